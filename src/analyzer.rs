@@ -0,0 +1,151 @@
+use crate::audio::AudioBuffer;
+use crate::cqt::ConstantQAnalyzer;
+use crate::error::{FftvizError, Result};
+use crate::fft::{SpectrumAnalyzer, FFT};
+
+// Turns frames of mono samples into band magnitudes. Frames are `frame_size`
+// samples long and start once every 1 / fft_fps seconds.
+pub trait Analyzer: Send {
+    fn frame_size(&self) -> usize;
+
+    // Centre frequency of each band returned by `analyze`
+    fn frequencies(&self) -> Vec<f32>;
+
+    fn analyze(&mut self, frame: &[f32]) -> Result<Vec<f32>>;
+}
+
+// Everything an analyzer may be configured with
+#[derive(Debug, Clone)]
+pub struct AnalyzerParams {
+    pub sample_rate: u32,
+    pub n_channels: usize,
+    pub freq_resolution: u32,
+    pub min_freq: f32,
+    pub max_freq: f32,
+    pub bins_per_octave: u32,
+}
+
+// How far apart an analyzer's bands are, so callers can tell how narrow a
+// min_freq..max_freq range may get before it runs out of bands
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BandSpacing {
+    // Bands a fixed number of Hz apart
+    Linear(f32),
+    // Bands a fixed ratio apart, starting no lower than `lowest`
+    Log { ratio: f32, lowest: f32 },
+}
+
+impl BandSpacing {
+    // Lowest max_freq leaving room for `bands` bands from `min_freq` up
+    pub fn max_freq_above(&self, min_freq: f32, bands: usize) -> f32 {
+        match *self {
+            BandSpacing::Linear(step) => min_freq + step * bands as f32,
+            BandSpacing::Log { ratio, lowest } => min_freq.max(lowest) * ratio.powi(bands as i32),
+        }
+    }
+
+    // Highest min_freq leaving room for `bands` bands up to `max_freq`
+    pub fn min_freq_below(&self, max_freq: f32, bands: usize) -> f32 {
+        match *self {
+            BandSpacing::Linear(step) => max_freq - step * bands as f32,
+            BandSpacing::Log { ratio, .. } => max_freq / ratio.powi(bands as i32),
+        }
+    }
+}
+
+pub struct AnalyzerEntry {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub build: fn(&AnalyzerParams) -> Result<Box<dyn Analyzer>>,
+    pub band_spacing: fn(&AnalyzerParams) -> BandSpacing,
+}
+
+// Available analyzers, selectable by name with --analyzer or in config.yaml
+pub const ANALYZERS: &[AnalyzerEntry] = &[
+    AnalyzerEntry {
+        name: "fft",
+        description: "Linear FFT bins (uses freq_resolution)",
+        build: |p| {
+            Ok(Box::new(SpectrumAnalyzer::new(
+                p.sample_rate,
+                p.n_channels,
+                p.freq_resolution,
+                p.min_freq,
+                p.max_freq,
+            )?))
+        },
+        band_spacing: |p| SpectrumAnalyzer::band_spacing(p.sample_rate, p.freq_resolution),
+    },
+    AnalyzerEntry {
+        name: "cqt",
+        description: "Constant-Q transform (uses bins_per_octave)",
+        build: |p| {
+            Ok(Box::new(ConstantQAnalyzer::new(
+                p.sample_rate,
+                p.bins_per_octave,
+                p.min_freq,
+                p.max_freq,
+            )))
+        },
+        band_spacing: |p| ConstantQAnalyzer::band_spacing(p.bins_per_octave),
+    },
+];
+
+pub fn analyzer_names() -> Vec<&'static str> {
+    ANALYZERS.iter().map(|a| a.name).collect()
+}
+
+pub fn build_analyzer(name: &str, params: &AnalyzerParams) -> Result<Box<dyn Analyzer>> {
+    match ANALYZERS.iter().find(|a| a.name == name) {
+        Some(a) => (a.build)(params),
+        None => Err(FftvizError::InvalidValue(format!(
+            "analyzer must be one of: {}.",
+            analyzer_names().join(", ")
+        ))),
+    }
+}
+
+// Band spacing of the analyzer called `name`, None if there is no such analyzer
+pub fn band_spacing(name: &str, params: &AnalyzerParams) -> Option<BandSpacing> {
+    ANALYZERS
+        .iter()
+        .find(|a| a.name == name)
+        .map(|a| (a.band_spacing)(params))
+}
+
+// Runs an analyzer over the whole track, one frame per fft frame
+pub fn run_analyzer(audio: &AudioBuffer, analyzer: &mut dyn Analyzer, fft_fps: u32) -> Result<FFT> {
+    let hop = (audio.sample_rate / fft_fps) as usize;
+    let frame_size = analyzer.frame_size();
+    let (mut min, mut max): (f32, f32) = (100.0, 0.0);
+    let mut output_vec = Vec::new();
+
+    let mut start = 0;
+    while start < audio.num_frames() {
+        let curr_vec = analyzer.analyze(&audio.mono_frame(start, frame_size))?;
+        for val in curr_vec.iter() {
+            max = max.max(*val);
+            min = min.min(*val);
+        }
+        output_vec.push(curr_vec);
+        start += hop;
+    }
+
+    let freqs = analyzer.frequencies();
+    if freqs.is_empty() {
+        return Err(FftvizError::Analysis(String::from(
+            "no frequency bands between min_freq and max_freq",
+        )));
+    }
+
+    let num_frames = output_vec.len();
+    let num_bars = freqs.len();
+    Ok(FFT {
+        fft: output_vec,
+        freqs,
+        num_frames,
+        num_bars,
+        min,
+        max,
+    })
+}
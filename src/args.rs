@@ -1,14 +1,35 @@
 use crate::*;
 use bevy::prelude::*;
-use clap::{ArgAction, Parser};
-use std::path::Path;
+use clap::builder::PossibleValuesParser;
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
 
 #[derive(Debug, Parser)]
 #[clap(author, version, about)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct CLIArgs {
-    /// File path to Audio file
-    #[arg()]
-    file_path: String,
+    #[command(subcommand)]
+    pub command: Option<CLICommand>,
+
+    /// Audio files to play in order
+    #[arg(required = true)]
+    file_paths: Vec<String>,
+
+    /// Config file to use instead of ~/.config/fftviz/config.yaml
+    #[arg(long = "config", default_value = None)]
+    pub config: Option<PathBuf>,
+
+    /// Named profile from config.yaml to take settings from
+    #[arg(long = "profile", default_value = None)]
+    pub profile: Option<String>,
+
+    /// Start playing at this time (seconds, M:SS or H:MM:SS)
+    #[arg(long = "start", value_parser = parse_time, default_value = None)]
+    pub start: Option<f64>,
+
+    /// Stop playing at this time (seconds, M:SS or H:MM:SS)
+    #[arg(long = "end", value_parser = parse_time, default_value = None)]
+    pub end: Option<f64>,
 
     /// Smoothing factor for spatial interpolation between bars
     #[clap(long = "smoothness", default_value = None)]
@@ -26,10 +47,22 @@ pub struct CLIArgs {
     #[arg(long = "max-freq", default_value = None)]
     pub max_freq: Option<f32>,
 
+    /// Analyzer used to compute bar magnitudes
+    #[arg(long = "analyzer", value_parser = PossibleValuesParser::new(analyzer_names()), default_value = None)]
+    pub analyzer: Option<String>,
+
+    /// Frequency bins per octave for the constant-Q analyzer
+    #[arg(long = "bins-per-octave", default_value = None)]
+    pub bins_per_octave: Option<u32>,
+
     /// Volume
     #[arg(long = "volume", default_value = None)]
     pub volume: Option<u32>,
 
+    /// Playback speed, from 0.5 to 2 (pitch changes with speed)
+    #[arg(long = "speed", default_value = None)]
+    pub speed: Option<f32>,
+
     /// Window width
     #[arg(long = "width", default_value = None)]
     pub window_width: Option<f32>,
@@ -42,31 +75,160 @@ pub struct CLIArgs {
     #[arg(long = "border-size", default_value = None)]
     pub border_size: Option<i32>,
 
-    /// Border color for each bar (in hex)
+    /// Border color for each bar (hex or name)
     #[arg(long = "border-color", default_value = None)]
     pub border_color: Option<String>,
 
-    /// Color for each bar (in hex)
+    /// Color for each bar (hex or name)
     #[arg(long = "bar-color", default_value = None)]
     pub bar_color: Option<String>,
 
     /// Use if you want track name to be printed
-    #[arg(long = "track-name", action = ArgAction::SetTrue)]
-    pub track_name: Option<bool>,
+    #[arg(long = "track-name", overrides_with = "no_track_name")]
+    pub track_name: bool,
+
+    /// Use to hide the track name
+    #[arg(long = "no-track-name", overrides_with = "track_name")]
+    pub no_track_name: bool,
 
     /// Use if you want the gui to be open when launched
-    #[arg(long = "display-gui", action = ArgAction::SetTrue)]
-    pub display_gui: Option<bool>,
+    #[arg(long = "display-gui", overrides_with = "no_display_gui")]
+    pub display_gui: bool,
+
+    /// Use to keep the gui closed when launched
+    #[arg(long = "no-display-gui", overrides_with = "display_gui")]
+    pub no_display_gui: bool,
+
+    /// Use if you want the stereo vectorscope to be shown when launched
+    #[arg(long = "vectorscope", overrides_with = "no_vectorscope")]
+    pub vectorscope: bool,
+
+    /// Use to hide the stereo vectorscope when launched
+    #[arg(long = "no-vectorscope", overrides_with = "vectorscope")]
+    pub no_vectorscope: bool,
+
+    /// Use if you want the level meters to be shown when launched
+    #[arg(long = "level-meters", overrides_with = "no_level_meters")]
+    pub level_meters: bool,
+
+    /// Use to hide the level meters when launched
+    #[arg(long = "no-level-meters", overrides_with = "level_meters")]
+    pub no_level_meters: bool,
+
+    /// Use if you want tuner mode (detected note and cents deviation) when launched
+    #[arg(long = "tuner", overrides_with = "no_tuner")]
+    pub tuner: bool,
+
+    /// Use to turn tuner mode off when launched
+    #[arg(long = "no-tuner", overrides_with = "tuner")]
+    pub no_tuner: bool,
+
+    /// Use if you want a piano keyboard axis under the bars
+    #[arg(long = "piano-axis", overrides_with = "no_piano_axis")]
+    pub piano_axis: bool,
+
+    /// Use to hide the piano keyboard axis
+    #[arg(long = "no-piano-axis", overrides_with = "piano_axis")]
+    pub no_piano_axis: bool,
+
+    /// Use if you want frequency labels, dB gridlines and a hover inspector
+    #[arg(long = "axis", overrides_with = "no_axis")]
+    pub axis: bool,
+
+    /// Use to hide frequency labels, dB gridlines and the hover inspector
+    #[arg(long = "no-axis", overrides_with = "axis")]
+    pub no_axis: bool,
+
+    /// Chromagram (pitch class) view with estimated key
+    #[arg(long = "chroma", value_enum, default_value = None)]
+    pub chroma: Option<ChromaView>,
+
+    /// What to do when a track ends: quit, loop it, hold the last frame or
+    /// play the next file
+    #[arg(long = "on-end", value_enum, default_value = None)]
+    pub on_end: Option<OnEnd>,
+
+    /// Keep the seek bar visible as a progress bar along this edge
+    #[arg(long = "progress-bar", value_enum, default_value = None)]
+    pub progress_bar: Option<ProgressBar>,
+
+    /// Thickness of the seek and progress bar in pixels
+    #[arg(long = "progress-bar-thickness", default_value = None)]
+    pub progress_bar_thickness: Option<f32>,
+
+    /// Color of the played part of the seek and progress bar (hex or name)
+    #[arg(long = "progress-bar-color", default_value = None)]
+    pub progress_bar_color: Option<String>,
+
+    /// Use if you want the elapsed and remaining time to be shown
+    #[arg(long = "time-label", overrides_with = "no_time_label")]
+    pub time_label: bool,
+
+    /// Use to hide the elapsed and remaining time
+    #[arg(long = "no-time-label", overrides_with = "time_label")]
+    pub no_time_label: bool,
 
     /// Use if you want to display debug information when launching
-    #[arg(long = "debug", action = ArgAction::SetTrue)]
-    pub debug: Option<bool>,
+    #[arg(long = "debug", overrides_with = "no_debug")]
+    pub debug: bool,
+
+    /// Use to hide debug information
+    #[arg(long = "no-debug", overrides_with = "debug")]
+    pub no_debug: bool,
+
+    /// Use to enable the title bar
+    #[arg(long = "title-bar", overrides_with = "no_title_bar")]
+    pub title_bar: bool,
 
     /// Use to disable the title bar
-    #[arg(long = "no-title-bar", action = ArgAction::SetFalse)]
-    pub title_bar: Option<bool>,
+    #[arg(long = "no-title-bar", overrides_with = "title_bar")]
+    pub no_title_bar: bool,
+
+    /// Use to open in exclusive fullscreen
+    #[arg(long = "fullscreen", overrides_with_all = ["borderless_fullscreen", "windowed"])]
+    pub fullscreen: bool,
+
+    /// Use to open in a borderless window covering the monitor
+    #[arg(long = "borderless-fullscreen", overrides_with_all = ["fullscreen", "windowed"])]
+    pub borderless_fullscreen: bool,
+
+    /// Use to open in a window
+    #[arg(long = "windowed", overrides_with_all = ["fullscreen", "borderless_fullscreen"])]
+    pub windowed: bool,
+
+    /// Window position in pixels from the top left of the desktop, or "center"
+    #[arg(long = "position", value_name = "X,Y", allow_hyphen_values = true, default_value = None)]
+    pub position: Option<String>,
+
+    /// Monitor to open the window on, starting at 0
+    #[arg(long = "monitor", value_name = "N", default_value = None)]
+    pub monitor: Option<usize>,
+
+    /// Use to keep the window above other windows
+    #[arg(long = "always-on-top", overrides_with = "no_always_on_top")]
+    pub always_on_top: bool,
+
+    /// Use to let other windows cover the window
+    #[arg(long = "no-always-on-top", overrides_with = "always_on_top")]
+    pub no_always_on_top: bool,
+
+    /// Use to let the desktop show through a transparent background color
+    #[arg(long = "transparent", overrides_with = "no_transparent")]
+    pub transparent: bool,
+
+    /// Use to draw the background color opaque
+    #[arg(long = "no-transparent", overrides_with = "transparent")]
+    pub no_transparent: bool,
+
+    /// Use to show only the bars in a click-through window along the bottom of the screen
+    #[arg(long = "overlay", overrides_with = "no_overlay")]
+    pub overlay: bool,
 
-    /// Color for currently playing text (in hex)
+    /// Use to open a normal window
+    #[arg(long = "no-overlay", overrides_with = "overlay")]
+    pub no_overlay: bool,
+
+    /// Color for currently playing text (hex or name)
     #[arg(long = "text-color", default_value = None)]
     pub text_color: Option<String>,
 
@@ -74,60 +236,292 @@ pub struct CLIArgs {
     #[arg(long = "font-size", default_value = None)]
     pub font_size: Option<i32>,
 
-    // Background color (in hex)
+    /// Currently playing label, with {title}, {artist}, {album}, {year}, {file},
+    /// {elapsed}, {duration} and {remaining} filled in
+    #[arg(long = "title-template", default_value = None)]
+    pub title_template: Option<String>,
+
+    /// Show the embedded cover art
+    #[arg(long = "album-art", value_enum, default_value = None)]
+    pub album_art: Option<AlbumArt>,
+
+    // Background color (hex or name)
     #[arg(long = "background-color", default_value = None)]
     pub background_color: Option<String>,
 }
 
-pub fn cli_args_to_fft_args(mut cli_args: CLIArgs, use_default: bool) -> FFTArgs {
-    if !Path::new(&cli_args.file_path).is_file() {
-        println!("File \"{}\" not found!", cli_args.file_path);
-        std::process::exit(1);
+#[derive(Debug, Subcommand)]
+pub enum CLICommand {
+    /// Print integrated loudness and true peak without opening a window
+    Loudness {
+        /// File path to Audio file
+        file_path: String,
+    },
+    /// Inspect config.yaml
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommand {
+    /// Validate a config file, printing every error and unknown key
+    Check {
+        /// Config file to check (defaults to the config file fftviz would use)
+        path: Option<PathBuf>,
+    },
+    /// Print config.yaml as fftviz reads it
+    Show {
+        /// Print every setting after applying defaults, config files, profile and
+        /// FFTVIZ_* env vars, along with where its value came from
+        #[arg(long = "resolved")]
+        resolved: bool,
+
+        /// Profile to resolve settings for
+        #[arg(long = "profile")]
+        profile: Option<String>,
+
+        /// Config file to use instead of ~/.config/fftviz/config.yaml
+        #[arg(long = "config")]
+        config: Option<PathBuf>,
+    },
+}
+
+// Parses a time like "90", "1:30", "1:30.5" or "1:02:03" into seconds
+pub fn parse_time(value: &str) -> std::result::Result<f64, String> {
+    let invalid = || format!("\"{}\" is not a time like 90, 1:30 or 1:02:03.", value);
+    let parts = value.trim().split(':').collect::<Vec<&str>>();
+    if parts.len() > 3 {
+        return Err(invalid());
     }
 
-    // Merges cli args with args in config.yaml.
-    // Precendence: Cli args > config.yaml args > default values
-    merge_config_with_cli_args(&mut cli_args, use_default);
-
-    bar_smoothness_constraint(cli_args.smoothness.unwrap());
-    freq_resolution_constraint(cli_args.freq_resolution.unwrap());
-
-    FFTArgs {
-        file_path: Path::new(&cli_args.file_path).to_path_buf(),
-        border_size: cli_args.border_size.unwrap(),
-        border_color: Color::hex(cli_args.border_color.unwrap()).unwrap(),
-        bar_color: Color::hex(cli_args.bar_color.unwrap()).unwrap(),
-        track_name: cli_args.track_name.unwrap(),
-        text_color: Color::hex(cli_args.text_color.unwrap()).unwrap(),
-        font_size: cli_args.font_size.unwrap(),
-        background_color: Color::hex(cli_args.background_color.unwrap()).unwrap(),
-        smoothness: cli_args.smoothness.unwrap(),
-        freq_resolution: cli_args.freq_resolution.unwrap(),
-        window_height: cli_args.window_height.unwrap(),
-        window_width: cli_args.window_width.unwrap(),
-        min_freq: cli_args.min_freq.unwrap(),
-        max_freq: cli_args.max_freq.unwrap(),
-        display_gui: cli_args.display_gui.unwrap(),
-        title_bar: cli_args.title_bar.unwrap(),
-        volume: cli_args.volume.unwrap(),
-        debug: cli_args.debug.unwrap(),
+    let mut seconds = 0.0;
+    for (i, part) in parts.iter().enumerate() {
+        let last = i == parts.len() - 1;
+        let n = match last {
+            true => part.parse::<f64>().ok(),
+            false => part.parse::<u32>().ok().map(|n| n as f64),
+        }
+        .filter(|n| n.is_finite() && *n >= 0.0)
+        .ok_or_else(invalid)?;
+        // Minutes and seconds after a colon must be below 60
+        if i > 0 && n >= 60.0 {
+            return Err(invalid());
+        }
+        seconds = seconds * 60.0 + n;
     }
+    Ok(seconds)
 }
 
-pub fn parse_cli_args() -> FFTArgs {
-    cli_args_to_fft_args(args::CLIArgs::parse(), false)
+impl CLIArgs {
+    // Window mode from --fullscreen, --borderless-fullscreen or --windowed
+    pub fn fullscreen_mode(&self) -> Option<Fullscreen> {
+        match (self.fullscreen, self.borderless_fullscreen, self.windowed) {
+            (true, _, _) => Some(Fullscreen::Exclusive),
+            (_, true, _) => Some(Fullscreen::Borderless),
+            (_, _, true) => Some(Fullscreen::Off),
+            _ => None,
+        }
+    }
 }
-// Value constraints
-pub fn bar_smoothness_constraint(v: u32) {
-    if v > 3 {
-        println!("smoothness must be between 0 and 3 inclusive.");
-        std::process::exit(1);
+
+// Value of a --X/--no-X pair of flags, None if neither was given
+pub fn flag(enabled: bool, disabled: bool) -> Option<bool> {
+    match (enabled, disabled) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
+    }
+}
+
+// Command line and FFTVIZ_* env settings, read once at startup. Config
+// reloads, profile switches and resets layer the config files under these
+// again, without parsing argv or reading the environment.
+#[derive(Resource, Clone, Debug)]
+pub struct ArgLayers {
+    cli: ConfigFFTArgs,
+    env: ConfigFFTArgs,
+    playlist: Vec<PathBuf>,
+    config_path: Option<PathBuf>,
+    // From --profile or FFTVIZ_PROFILE
+    pub profile: Option<String>,
+    start: f64,
+    end: Option<f64>,
+    debug: bool,
+    // Settings as last resolved, before any changes made in the app
+    pub resolved: ConfigFFTArgs,
+}
+
+impl ArgLayers {
+    pub fn new(cli_args: CLIArgs) -> Result<ArgLayers> {
+        let playlist = cli_args
+            .file_paths
+            .iter()
+            .map(PathBuf::from)
+            .collect::<Vec<PathBuf>>();
+        if let Some(missing) = playlist.iter().find(|p| !p.is_file()) {
+            return Err(FftvizError::FileNotFound(missing.clone()));
+        }
+        let start = cli_args.start.unwrap_or(0.0);
+        if cli_args.end.is_some_and(|end| end <= start) {
+            return Err(FftvizError::InvalidValue(String::from(
+                "--end must be after --start.",
+            )));
+        }
+
+        Ok(ArgLayers {
+            cli: ConfigFFTArgs::from(&cli_args),
+            env: env_layer()?,
+            playlist,
+            config_path: config_path(cli_args.config.as_deref())?,
+            profile: cli_args.profile.clone().or_else(env_profile),
+            start,
+            end: cli_args.end,
+            debug: flag(cli_args.debug, cli_args.no_debug).unwrap_or(false),
+            resolved: ConfigFFTArgs::default(),
+        })
+    }
+
+    // Layers for settings that were not read from a command line, e.g. when
+    // embedding the plugin. Reloads then only layer the config files.
+    pub fn from_args(args: &FFTArgs) -> ArgLayers {
+        ArgLayers {
+            cli: ConfigFFTArgs::empty(),
+            env: ConfigFFTArgs::empty(),
+            playlist: args.playlist.clone(),
+            config_path: args.config_path.clone(),
+            profile: args.profile.clone(),
+            start: args.start,
+            end: args.end,
+            debug: args.debug,
+            resolved: fftargs_to_config(args, &ConfigFFTArgs::empty()),
+        }
+    }
+
+    // Layers settings with increasing precedence: defaults, system config,
+    // user config.yaml and `profile`, FFTVIZ_* env vars, CLI args. Resetting
+    // to default skips the config files.
+    pub fn resolve(&mut self, profile: Option<String>, use_config_files: bool) -> Result<FFTArgs> {
+        let settings = resolve_settings(
+            self.cli.clone(),
+            self.env.clone(),
+            profile.as_deref(),
+            self.config_path.as_deref(),
+            use_config_files,
+        )?;
+        settings.validate()?;
+        let args = self.fft_args(settings.values.clone(), profile)?;
+        self.resolved = settings.values;
+        Ok(args)
+    }
+
+    // FFTArgs with the settings in `v`, which must all be set
+    pub fn fft_args(&self, v: ConfigFFTArgs, profile: Option<String>) -> Result<FFTArgs> {
+        Ok(FFTArgs {
+            file_path: self.playlist.first().cloned().unwrap_or_default(),
+            playlist: self.playlist.clone(),
+            config_path: self.config_path.clone(),
+            profile,
+            start: self.start,
+            end: self.end,
+            border_size: v.border_size.unwrap(),
+            border_color: parse_color("border-color", &v.border_color.unwrap())?,
+            bar_color: parse_color("bar-color", &v.bar_color.unwrap())?,
+            track_name: v.display_track_name.unwrap(),
+            text_color: parse_color("text-color", &v.text_color.unwrap())?,
+            font_size: v.font_size.unwrap(),
+            title_template: v.title_template.unwrap(),
+            album_art: v.album_art.unwrap(),
+            background_color: parse_color("background-color", &v.background_color.unwrap())?,
+            smoothness: v.smoothness.unwrap(),
+            freq_resolution: v.freq_resolution.unwrap(),
+            window_height: v.window_height.unwrap(),
+            window_width: v.window_width.unwrap(),
+            min_freq: v.min_freq.unwrap(),
+            max_freq: v.max_freq.unwrap(),
+            display_gui: v.display_gui.unwrap(),
+            vectorscope: v.vectorscope.unwrap(),
+            level_meters: v.level_meters.unwrap(),
+            tuner: v.tuner.unwrap(),
+            piano_axis: v.piano_axis.unwrap(),
+            axis: v.axis.unwrap(),
+            chroma: v.chroma.unwrap(),
+            on_end: v.on_end.unwrap(),
+            progress_bar: v.progress_bar.unwrap(),
+            progress_bar_thickness: v.progress_bar_thickness.unwrap(),
+            progress_bar_color: parse_color("progress-bar-color", &v.progress_bar_color.unwrap())?,
+            time_label: v.time_label.unwrap(),
+            analyzer: v.analyzer.unwrap(),
+            bins_per_octave: v.bins_per_octave.unwrap(),
+            title_bar: v.title_bar.unwrap(),
+            fullscreen: v.fullscreen.unwrap(),
+            position: parse_position(&v.position.unwrap())?,
+            monitor: v.monitor.unwrap(),
+            always_on_top: v.always_on_top.unwrap(),
+            transparent: v.transparent.unwrap(),
+            overlay: v.overlay.unwrap(),
+            volume: v.volume.unwrap(),
+            speed: v.speed.unwrap(),
+            keybindings: Keybindings::resolve(v.keybindings.as_ref()).0,
+            debug: self.debug,
+        })
     }
 }
 
-fn freq_resolution_constraint(v: u32) {
-    if v < 10 || v > 300 {
-        println!("freq-resolution must be between 10 and 300 inclusive.");
-        std::process::exit(1);
+pub fn cli_args_to_fft_args(cli_args: CLIArgs, use_default: bool) -> Result<FFTArgs> {
+    let mut layers = ArgLayers::new(cli_args)?;
+    let profile = match use_default {
+        true => None,
+        false => layers.profile.clone(),
+    };
+    layers.resolve(profile, !use_default)
+}
+
+pub fn parse_cli_args() -> Result<FFTArgs> {
+    cli_args_to_fft_args(args::CLIArgs::parse(), false)
+}
+
+pub fn parse_color(field: &'static str, value: &str) -> Result<Color> {
+    parse_color_str(value).ok_or_else(|| FftvizError::InvalidColor {
+        field,
+        value: value.to_string(),
+    })
+}
+
+pub fn parse_position(value: &str) -> Result<Placement> {
+    Placement::parse(value).ok_or_else(|| {
+        FftvizError::InvalidValue(format!(
+            "position \"{}\" must be \"center\" or a position like 100,50.",
+            value
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_times() {
+        assert_eq!(parse_time("90"), Ok(90.0));
+        assert_eq!(parse_time("1:30"), Ok(90.0));
+        assert_eq!(parse_time(" 1:30.5 "), Ok(90.5));
+        assert_eq!(parse_time("1:02:03"), Ok(3723.0));
+        assert_eq!(parse_time("0.25"), Ok(0.25));
+    }
+
+    #[test]
+    fn rejects_invalid_times() {
+        for value in ["", "-5", "1:60", "1:2:3:4", "1.5:00", "a:30", "1:", "inf", "NaN"] {
+            assert!(parse_time(value).is_err(), "{:?} was accepted", value);
+        }
+    }
+
+    #[test]
+    fn flag_pairs() {
+        assert_eq!(flag(true, false), Some(true));
+        assert_eq!(flag(false, true), Some(false));
+        assert_eq!(flag(false, false), None);
     }
 }
@@ -0,0 +1,148 @@
+use crate::error::{FftvizError, Result};
+use bevy::prelude::Resource;
+use rodio::{source::Source, Decoder};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+// Decoded, interleaved time-domain samples for the whole track. Samples are
+// shared, so cloning is cheap.
+#[derive(Resource, Clone)]
+pub struct AudioBuffer {
+    pub samples: Arc<Vec<i16>>,
+    pub n_channels: usize,
+    pub sample_rate: u32,
+}
+
+impl AudioBuffer {
+    pub fn num_frames(&self) -> usize {
+        self.samples.len() / self.n_channels
+    }
+
+    // Frames from `start` to `end` seconds (or the end of the track)
+    pub fn slice(&self, start: f64, end: Option<f64>) -> AudioBuffer {
+        let frame = |t: f64| ((t * self.sample_rate as f64) as usize).min(self.num_frames());
+        let start = frame(start);
+        let end = end.map_or(self.num_frames(), frame).max(start);
+        AudioBuffer {
+            samples: Arc::new(self.samples[start * self.n_channels..end * self.n_channels].to_vec()),
+            ..self.clone()
+        }
+    }
+
+    // Plays the buffer from `seconds` into it, sharing its samples
+    pub fn source_from(&self, seconds: f64) -> BufferSource {
+        let frame = ((seconds.max(0.0) * self.sample_rate as f64) as usize).min(self.num_frames());
+        BufferSource {
+            samples: self.samples.clone(),
+            n_channels: self.n_channels as u16,
+            sample_rate: self.sample_rate,
+            pos: frame * self.n_channels,
+        }
+    }
+
+    // Returns the (left, right) pair for a frame scaled to [-1, 1].
+    // Mono files return the same sample on both sides.
+    pub fn stereo_frame(&self, i: usize) -> (f32, f32) {
+        let start = i * self.n_channels;
+        let l = self.samples[start] as f32 / i16::MAX as f32;
+        let r = match self.n_channels {
+            1 => l,
+            _ => self.samples[start + 1] as f32 / i16::MAX as f32,
+        };
+        (l, r)
+    }
+
+    // Channel-averaged samples for the `len` frames starting at frame `start`,
+    // zero padded past the end of the track
+    pub fn mono_frame(&self, start: usize, len: usize) -> Vec<f32> {
+        let end = (start + len).min(self.num_frames());
+        let mut frame = self.mono_window(end, end.saturating_sub(start));
+        frame.resize(len, 0.0);
+        frame
+    }
+
+    // Channel-averaged samples for the `len` frames ending at frame `end`
+    pub fn mono_window(&self, end: usize, len: usize) -> Vec<f32> {
+        let end = end.min(self.num_frames());
+        self.samples[end.saturating_sub(len) * self.n_channels..end * self.n_channels]
+            .chunks(self.n_channels)
+            .map(|frame| {
+                frame.iter().map(|x| *x as f32).sum::<f32>()
+                    / (self.n_channels as f32 * i16::MAX as f32)
+            })
+            .collect()
+    }
+}
+
+// Source playing an AudioBuffer, so that playback can start anywhere without
+// decoding the file again
+pub struct BufferSource {
+    samples: Arc<Vec<i16>>,
+    n_channels: u16,
+    sample_rate: u32,
+    pos: usize,
+}
+
+impl Iterator for BufferSource {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        let sample = self.samples.get(self.pos).copied();
+        self.pos += 1;
+        sample
+    }
+}
+
+impl Source for BufferSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        Some(self.samples.len().saturating_sub(self.pos))
+    }
+
+    fn channels(&self) -> u16 {
+        self.n_channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        let frames = self.samples.len() / self.n_channels as usize;
+        Some(Duration::from_secs_f64(frames as f64 / self.sample_rate as f64))
+    }
+}
+
+pub fn open_audio(audio_path: &PathBuf) -> Result<Decoder<BufReader<File>>> {
+    let file = File::open(audio_path).map_err(|source| match source.kind() {
+        std::io::ErrorKind::NotFound => FftvizError::FileNotFound(audio_path.clone()),
+        _ => FftvizError::Io {
+            path: audio_path.clone(),
+            source,
+        },
+    })?;
+    Decoder::new(BufReader::new(file)).map_err(|source| FftvizError::Decode {
+        path: audio_path.clone(),
+        source,
+    })
+}
+
+pub fn decode_audio(audio_path: &PathBuf) -> Result<AudioBuffer> {
+    let source = open_audio(audio_path)?;
+
+    let n_channels = source.channels() as usize;
+    let sample_rate = source.sample_rate();
+    let samples = source.collect::<Vec<i16>>();
+
+    if n_channels == 0 || samples.len() < n_channels {
+        return Err(FftvizError::EmptyAudio(audio_path.clone()));
+    }
+
+    Ok(AudioBuffer {
+        samples: Arc::new(samples),
+        n_channels,
+        sample_rate,
+    })
+}
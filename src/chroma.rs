@@ -0,0 +1,185 @@
+use crate::fft::FFT;
+use crate::pitch::{frequency_to_midi, NOTE_NAMES};
+use bevy::prelude::Resource;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+// Pitch classes in circle-of-fifths order
+pub const CIRCLE_OF_FIFTHS: [usize; 12] = [0, 7, 2, 9, 4, 11, 6, 1, 8, 3, 10, 5];
+
+// Krumhansl-Kessler key profiles, starting at the tonic
+const MAJOR_PROFILE: [f32; 12] = [
+    6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88,
+];
+const MINOR_PROFILE: [f32; 12] = [
+    6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17,
+];
+
+// Lowest frequency folded into the chromagram (A0)
+const MIN_CHROMA_FREQ: f32 = 27.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ChromaView {
+    Off,
+    Bars,
+    Wheel,
+}
+
+impl ChromaView {
+    pub fn next(&self) -> ChromaView {
+        match self {
+            ChromaView::Off => ChromaView::Bars,
+            ChromaView::Bars => ChromaView::Wheel,
+            ChromaView::Wheel => ChromaView::Off,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Key {
+    pub tonic: usize,
+    pub minor: bool,
+}
+
+impl Key {
+    // Camelot wheel notation used for harmonic mixing, e.g. "8A" for A minor
+    pub fn camelot(&self) -> String {
+        let relative_major = match self.minor {
+            true => (self.tonic + 3) % 12,
+            false => self.tonic,
+        };
+        let number = (relative_major * 7 + 7) % 12 + 1;
+        format!("{}{}", number, if self.minor { "A" } else { "B" })
+    }
+}
+
+impl std::fmt::Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mode = if self.minor { "minor" } else { "major" };
+        write!(f, "{} {} ({})", NOTE_NAMES[self.tonic], mode, self.camelot())
+    }
+}
+
+// Per-frame energy of each pitch class, scaled so the loudest class is 1
+#[derive(Resource, Debug)]
+pub struct Chroma {
+    pub frames: Vec<[f32; 12]>,
+    pub key: Key,
+}
+
+fn correlation(a: &[f32; 12], b: &[f32; 12]) -> f32 {
+    let (mean_a, mean_b) = (a.iter().sum::<f32>() / 12.0, b.iter().sum::<f32>() / 12.0);
+    let (mut cov, mut var_a, mut var_b) = (0.0, 0.0, 0.0);
+    for (x, y) in a.iter().zip(b.iter()) {
+        cov += (x - mean_a) * (y - mean_b);
+        var_a += (x - mean_a) * (x - mean_a);
+        var_b += (y - mean_b) * (y - mean_b);
+    }
+    match var_a * var_b {
+        d if d > 0.0 => cov / d.sqrt(),
+        _ => 0.0,
+    }
+}
+
+// Best matching key profile over all 24 major and minor keys
+pub fn estimate_key(chroma: &[f32; 12]) -> Key {
+    let mut best = (Key { tonic: 0, minor: false }, f32::MIN);
+    for tonic in 0..12 {
+        let mut rotated = [0.0; 12];
+        for (i, v) in rotated.iter_mut().enumerate() {
+            *v = chroma[(tonic + i) % 12];
+        }
+        for (profile, minor) in [(&MAJOR_PROFILE, false), (&MINOR_PROFILE, true)] {
+            let r = correlation(&rotated, profile);
+            if r > best.1 {
+                best = (Key { tonic, minor }, r);
+            }
+        }
+    }
+    best.0
+}
+
+// Folds the magnitude spectrum of every frame into 12 pitch classes
+pub fn compute_chroma(fft: &FFT) -> Chroma {
+    let pitch_classes = fft
+        .freqs
+        .iter()
+        .map(|f| match *f >= MIN_CHROMA_FREQ {
+            true => Some((frequency_to_midi(*f).round() as i32).rem_euclid(12) as usize),
+            false => None,
+        })
+        .collect::<Vec<Option<usize>>>();
+
+    let mut total = [0.0; 12];
+    let frames = fft
+        .fft
+        .iter()
+        .map(|frame| {
+            let mut chroma = [0.0; 12];
+            for (v, pc) in frame.iter().zip(pitch_classes.iter()) {
+                if let Some(pc) = pc {
+                    chroma[*pc] += v;
+                }
+            }
+            for (t, c) in total.iter_mut().zip(chroma.iter()) {
+                *t += c;
+            }
+
+            let max = chroma.iter().fold(0.0f32, |a, b| a.max(*b));
+            if max > 0.0 {
+                chroma.iter_mut().for_each(|c| *c /= max);
+            }
+            chroma
+        })
+        .collect::<Vec<[f32; 12]>>();
+
+    Chroma {
+        frames,
+        key: estimate_key(&total),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Chroma weighting the tonic triad of `tonic` over the rest of its scale
+    fn scale_chroma(tonic: usize, minor: bool) -> [f32; 12] {
+        let (triad, scale) = match minor {
+            true => ([0, 3, 7], [0, 2, 3, 5, 7, 8, 10]),
+            false => ([0, 4, 7], [0, 2, 4, 5, 7, 9, 11]),
+        };
+        let mut chroma = [0.0; 12];
+        for i in scale {
+            chroma[(tonic + i) % 12] = 0.5;
+        }
+        for i in triad {
+            chroma[(tonic + i) % 12] = 1.0;
+        }
+        chroma
+    }
+
+    #[test]
+    fn estimates_c_major_and_a_minor() {
+        let c_major = estimate_key(&scale_chroma(0, false));
+        assert_eq!((c_major.tonic, c_major.minor), (0, false));
+
+        let a_minor = estimate_key(&scale_chroma(9, true));
+        assert_eq!((a_minor.tonic, a_minor.minor), (9, true));
+        assert_eq!(a_minor.to_string(), "A minor (8A)");
+    }
+
+    #[test]
+    fn camelot_numbers_follow_the_circle_of_fifths() {
+        let camelot = |tonic, minor| Key { tonic, minor }.camelot();
+        assert_eq!(camelot(0, false), "8B");
+        assert_eq!(camelot(7, false), "9B");
+        assert_eq!(camelot(5, false), "7B");
+        assert_eq!(camelot(11, false), "1B");
+        // Relative minors share their major's number
+        assert_eq!(camelot(9, true), "8A");
+        assert_eq!(camelot(4, true), "9A");
+        assert_eq!(camelot(2, true), "7A");
+    }
+}
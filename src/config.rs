@@ -1,18 +1,22 @@
 use bevy::prelude::Color;
 use dirs::home_dir;
 use serde::{Deserialize, Serialize};
-use serde_yaml::{self};
-use std::fs::{create_dir_all, read_to_string, File, OpenOptions};
-use std::io::BufWriter;
-use std::{
-    fs::remove_file,
-    io::Write,
-    path::PathBuf,
-};
+use serde_yaml::{self, Value};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{create_dir_all, read_to_string};
+use std::io;
+use std::path::{Path, PathBuf};
 
-use crate::{CLIArgs, FFTArgs};
+use crate::config_edit::{locate, set_values, write_atomic};
+use crate::error::{FftvizError, Result};
+use crate::{
+    analyzer_names, flag, AlbumArt, CLIArgs, ChromaView, FFTArgs, Fullscreen, KeyList,
+    Keybindings, OnEnd, Placement, ProgressBar, MAX_SPEED, MIN_SPEED,
+};
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ConfigFFTArgs {
     pub border_size: Option<i32>,
     pub border_color: Option<String>,
@@ -20,16 +24,48 @@ pub struct ConfigFFTArgs {
     pub display_track_name: Option<bool>,
     pub text_color: Option<String>,
     pub font_size: Option<i32>,
+    pub title_template: Option<String>,
+    pub album_art: Option<AlbumArt>,
     pub background_color: Option<String>,
     pub smoothness: Option<u32>,
     pub freq_resolution: Option<u32>,
+    pub analyzer: Option<String>,
+    pub bins_per_octave: Option<u32>,
     pub window_width: Option<f32>,
     pub window_height: Option<f32>,
     pub min_freq: Option<f32>,
     pub max_freq: Option<f32>,
     pub display_gui: Option<bool>,
+    pub vectorscope: Option<bool>,
+    pub level_meters: Option<bool>,
+    pub tuner: Option<bool>,
+    pub piano_axis: Option<bool>,
+    pub axis: Option<bool>,
+    pub chroma: Option<ChromaView>,
+    pub on_end: Option<OnEnd>,
+    pub progress_bar: Option<ProgressBar>,
+    pub progress_bar_thickness: Option<f32>,
+    pub progress_bar_color: Option<String>,
+    pub time_label: Option<bool>,
     pub volume: Option<u32>,
+    pub speed: Option<f32>,
     pub title_bar: Option<bool>,
+    pub fullscreen: Option<Fullscreen>,
+    // "center" or "x,y"
+    pub position: Option<String>,
+    pub monitor: Option<usize>,
+    pub always_on_top: Option<bool>,
+    pub transparent: Option<bool>,
+    pub overlay: Option<bool>,
+    // Keys for each action, merged over the default bindings
+    pub keybindings: Option<BTreeMap<String, KeyList>>,
+    // Only valid inside a profile: profile to take unset values from. Profiles
+    // without a parent take them from the top-level settings.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inherits: Option<String>,
+    // Named sets of settings, selected with --profile
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profiles: Option<BTreeMap<String, ConfigFFTArgs>>,
 }
 
 impl Default for ConfigFFTArgs {
@@ -41,300 +77,1029 @@ impl Default for ConfigFFTArgs {
             display_track_name: Some(false),
             text_color: Some(String::from("FFFFFF")),
             font_size: Some(25),
+            title_template: Some(String::from("Playing: \"{file}\"")),
+            album_art: Some(AlbumArt::Off),
             background_color: Some(String::from("000000")),
             smoothness: Some(1),
             freq_resolution: Some(90),
+            analyzer: Some(String::from("fft")),
+            bins_per_octave: Some(12),
             window_width: Some(1000.0),
             window_height: Some(700.0),
             min_freq: Some(0.0),
             max_freq: Some(5000.0),
             display_gui: Some(false),
+            vectorscope: Some(false),
+            level_meters: Some(false),
+            tuner: Some(false),
+            piano_axis: Some(false),
+            axis: Some(false),
+            chroma: Some(ChromaView::Off),
+            on_end: Some(OnEnd::Quit),
+            progress_bar: Some(ProgressBar::Off),
+            progress_bar_thickness: Some(6.0),
+            progress_bar_color: Some(String::from("FFFFFF")),
+            time_label: Some(false),
             volume: Some(50),
+            speed: Some(1.0),
             title_bar: Some(true),
+            fullscreen: Some(Fullscreen::Off),
+            position: Some(String::from("center")),
+            monitor: Some(0),
+            always_on_top: Some(false),
+            transparent: Some(false),
+            overlay: Some(false),
+            keybindings: None,
+            inherits: None,
+            profiles: None,
         }
     }
 }
 
-pub fn config_path() -> PathBuf {
-    let mut config_path = home_dir().unwrap();
-    config_path.push(".config");
-    config_path.push("fftviz");
-    config_path.push("config.yaml");
-    config_path
+macro_rules! for_each_config_field {
+    ($m: ident, $child: expr, $parent: expr) => {
+        $m!(
+            $child,
+            $parent,
+            border_size,
+            border_color,
+            bar_color,
+            display_track_name,
+            text_color,
+            font_size,
+            title_template,
+            album_art,
+            background_color,
+            smoothness,
+            freq_resolution,
+            analyzer,
+            bins_per_octave,
+            window_width,
+            window_height,
+            min_freq,
+            max_freq,
+            display_gui,
+            vectorscope,
+            level_meters,
+            tuner,
+            piano_axis,
+            axis,
+            chroma,
+            on_end,
+            progress_bar,
+            progress_bar_thickness,
+            progress_bar_color,
+            time_label,
+            volume,
+            speed,
+            title_bar,
+            fullscreen,
+            position,
+            monitor,
+            always_on_top,
+            transparent,
+            overlay
+        )
+    };
 }
 
-pub fn config_exists() -> bool {
-    let cfg_path = config_path();
-    cfg_path.exists()
+macro_rules! inherit_fields {
+    ($child: expr, $parent: expr, $($field: ident),*) => {
+        $(
+            if $child.$field.is_none() {
+                $child.$field = $parent.$field.clone();
+            }
+        )*
+    };
 }
 
-pub fn read_config() -> ConfigFFTArgs {
-    let config_file = File::open(config_path()).expect("Could not find file lmao");
-    let user_config_yaml =
-        serde_yaml::from_reader(config_file).expect("Could not read values lmao");
-    user_config_yaml
+macro_rules! remove_inherited_fields {
+    ($child: expr, $parent: expr, $($field: ident),*) => {
+        $(
+            if $child.$field == $parent.$field {
+                $child.$field = None;
+            }
+        )*
+    };
 }
 
-macro_rules! update_cli_arg {
-    ($cli_arg: expr, $config_arg: expr, $default_config_arg: expr) => {
-        if let None = *$cli_arg {
-            if let None = $config_arg {
-                *$cli_arg = $default_config_arg;
-            } else {
-                *$cli_arg = $config_arg;
+macro_rules! remove_unset_fields {
+    ($child: expr, $other: expr, $($field: ident),*) => {
+        $(
+            if $other.$field.is_none() {
+                $child.$field = None;
             }
+        )*
+    };
+}
+
+impl ConfigFFTArgs {
+    // Fills every unset setting from `parent`
+    pub fn inherit(mut self, parent: &ConfigFFTArgs) -> ConfigFFTArgs {
+        for_each_config_field!(inherit_fields, self, parent);
+        // Keybindings are merged per action, so rebinding one keeps the others
+        if let Some(inherited) = &parent.keybindings {
+            let own = self.keybindings.get_or_insert_with(BTreeMap::new);
+            for (action, keys) in inherited {
+                own.entry(action.clone()).or_insert_with(|| keys.clone());
+            }
+        }
+        self
+    }
+
+    // Unsets every setting that is the same in `parent`
+    pub fn overrides_of(mut self, parent: &ConfigFFTArgs) -> ConfigFFTArgs {
+        for_each_config_field!(remove_inherited_fields, self, parent);
+        if let (Some(own), Some(inherited)) = (&mut self.keybindings, &parent.keybindings) {
+            own.retain(|action, keys| inherited.get(action) != Some(keys));
+        }
+        if self.keybindings.as_ref().is_some_and(|k| k.is_empty()) {
+            self.keybindings = None;
+        }
+        self
+    }
+
+    // Unsets every setting that is not set in `other`
+    pub fn only_keys_of(mut self, other: &ConfigFFTArgs) -> ConfigFFTArgs {
+        for_each_config_field!(remove_unset_fields, self, other);
+        match &other.keybindings {
+            Some(other) => {
+                if let Some(own) = &mut self.keybindings {
+                    own.retain(|action, _| other.contains_key(action));
+                }
+            }
+            None => self.keybindings = None,
+        }
+        self
+    }
+
+    pub fn profile_names(&self) -> Vec<String> {
+        self.profiles
+            .as_ref()
+            .map(|p| p.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    // No settings set. Every field is optional, so this always parses.
+    pub fn empty() -> ConfigFFTArgs {
+        serde_yaml::from_str("{}").unwrap()
+    }
+
+    // Keys of the settings that are set
+    pub fn set_keys(&self) -> Vec<String> {
+        match serde_yaml::to_value(self) {
+            Ok(Value::Mapping(m)) => m
+                .into_iter()
+                .filter(|(_, v)| !v.is_null())
+                .filter_map(|(k, _)| k.as_str().map(String::from))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    // Top-level settings, without profiles
+    pub fn base(&self) -> ConfigFFTArgs {
+        ConfigFFTArgs {
+            inherits: None,
+            profiles: None,
+            ..self.clone()
+        }
+    }
+}
+
+// Settings set by profile `name` or the profiles it inherits from, following
+// `inherits` until a profile without a parent
+pub fn profile_chain(cfg: &ConfigFFTArgs, name: &str) -> Result<ConfigFFTArgs> {
+    let profiles = cfg.profiles.clone().unwrap_or_default();
+    let mut visited: Vec<String> = Vec::new();
+    let mut resolved: Option<ConfigFFTArgs> = None;
+    let mut curr = name.to_string();
+
+    loop {
+        if visited.contains(&curr) {
+            return Err(FftvizError::InvalidValue(format!(
+                "profiles inherit from each other in a cycle: {} -> {}.",
+                visited.join(" -> "),
+                curr
+            )));
+        }
+        let profile = profiles.get(&curr).ok_or_else(|| {
+            FftvizError::InvalidValue(format!(
+                "unknown profile \"{}\". Available profiles: {}.",
+                curr,
+                if profiles.is_empty() {
+                    String::from("none")
+                } else {
+                    cfg.profile_names().join(", ")
+                }
+            ))
+        })?;
+
+        resolved = Some(match resolved {
+            None => profile.clone(),
+            Some(r) => r.inherit(profile),
+        });
+        visited.push(curr);
+
+        match &profile.inherits {
+            Some(parent) => curr = parent.clone(),
+            None => break,
+        }
+    }
+
+    Ok(resolved.unwrap().base())
+}
+
+// Settings of profile `name`, unset values coming from the top-level settings
+pub fn resolve_profile(cfg: &ConfigFFTArgs, name: &str) -> Result<ConfigFFTArgs> {
+    Ok(profile_chain(cfg, name)?.inherit(&cfg.base()))
+}
+
+impl From<&CLIArgs> for ConfigFFTArgs {
+    fn from(args: &CLIArgs) -> Self {
+        ConfigFFTArgs {
+            border_size: args.border_size,
+            border_color: args.border_color.clone(),
+            bar_color: args.bar_color.clone(),
+            display_track_name: flag(args.track_name, args.no_track_name),
+            text_color: args.text_color.clone(),
+            font_size: args.font_size,
+            title_template: args.title_template.clone(),
+            album_art: args.album_art,
+            background_color: args.background_color.clone(),
+            smoothness: args.smoothness,
+            freq_resolution: args.freq_resolution,
+            analyzer: args.analyzer.clone(),
+            bins_per_octave: args.bins_per_octave,
+            window_width: args.window_width,
+            window_height: args.window_height,
+            min_freq: args.min_freq,
+            max_freq: args.max_freq,
+            display_gui: flag(args.display_gui, args.no_display_gui),
+            vectorscope: flag(args.vectorscope, args.no_vectorscope),
+            level_meters: flag(args.level_meters, args.no_level_meters),
+            tuner: flag(args.tuner, args.no_tuner),
+            piano_axis: flag(args.piano_axis, args.no_piano_axis),
+            axis: flag(args.axis, args.no_axis),
+            chroma: args.chroma,
+            on_end: args.on_end,
+            progress_bar: args.progress_bar,
+            progress_bar_thickness: args.progress_bar_thickness,
+            progress_bar_color: args.progress_bar_color.clone(),
+            time_label: flag(args.time_label, args.no_time_label),
+            volume: args.volume,
+            speed: args.speed,
+            title_bar: flag(args.title_bar, args.no_title_bar),
+            fullscreen: args.fullscreen_mode(),
+            position: args.position.clone(),
+            monitor: args.monitor,
+            always_on_top: flag(args.always_on_top, args.no_always_on_top),
+            transparent: flag(args.transparent, args.no_transparent),
+            overlay: flag(args.overlay, args.no_overlay),
+            keybindings: None,
+            inherits: None,
+            profiles: None,
+        }
+    }
+}
+
+// User config file, from the first of `explicit` (--config), FFTVIZ_CONFIG,
+// $XDG_CONFIG_HOME/fftviz/config.yaml and ~/.config/fftviz/config.yaml. A file
+// chosen with --config or FFTVIZ_CONFIG has to exist. None if neither
+// XDG_CONFIG_HOME nor HOME is set.
+pub fn config_path(explicit: Option<&Path>) -> Result<Option<PathBuf>> {
+    let explicit = explicit
+        .map(Path::to_path_buf)
+        .or_else(|| std::env::var_os("FFTVIZ_CONFIG").map(PathBuf::from))
+        .filter(|p| !p.as_os_str().is_empty());
+    if let Some(path) = explicit {
+        if !path.is_file() {
+            return Err(FftvizError::FileNotFound(path));
         }
+        return Ok(Some(path));
+    }
+
+    // Relative XDG_CONFIG_HOME values are invalid and ignored, as the spec asks
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| home_dir().map(|home| home.join(".config")));
+    Ok(config_home.map(|dir| dir.join("fftviz").join("config.yaml")))
+}
+
+// Settings shared by all users, below the user's config.yaml in precedence:
+// the first fftviz/config.yaml found in $XDG_CONFIG_DIRS, a colon-separated
+// list that defaults to /etc/xdg, then /etc/fftviz/config.yaml. None if there
+// is no such file.
+pub fn system_config_path() -> Option<PathBuf> {
+    system_config_path_in(
+        std::env::var_os("XDG_CONFIG_DIRS"),
+        Path::new(SYSTEM_CONFIG_FALLBACK),
+    )
+}
+
+const SYSTEM_CONFIG_FALLBACK: &str = "/etc/fftviz/config.yaml";
+
+fn system_config_path_in(config_dirs: Option<OsString>, fallback: &Path) -> Option<PathBuf> {
+    let config_dirs = config_dirs
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| OsString::from("/etc/xdg"));
+    // Relative entries are invalid and ignored, like XDG_CONFIG_HOME
+    std::env::split_paths(&config_dirs)
+        .filter(|dir| dir.is_absolute())
+        .map(|dir| dir.join("fftviz").join("config.yaml"))
+        .chain([fallback.to_path_buf()])
+        .find(|path| path.is_file())
+}
+
+// A problem found while checking a config file. Line and column are 1-based
+// and point at the offending value when known.
+#[derive(Debug, Clone)]
+pub struct ConfigDiagnostic {
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => write!(f, "line {}, column {}: {}", line, column, self.message),
+            (Some(line), None) => write!(f, "line {}: {}", line, self.message),
+            _ => write!(f, "{}", self.message),
+        }
+    }
+}
+
+pub struct ConfigReport {
+    // Parsed config, present unless the file could not be deserialized
+    pub config: Option<ConfigFFTArgs>,
+    pub errors: Vec<ConfigDiagnostic>,
+    pub warnings: Vec<ConfigDiagnostic>,
+}
+
+// Keys accepted in config.yaml, taken from the serialized defaults
+pub fn known_keys() -> Vec<String> {
+    match serde_yaml::to_value(ConfigFFTArgs::default()) {
+        Ok(Value::Mapping(m)) => m
+            .keys()
+            .filter_map(|k| k.as_str().map(String::from))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+// Line and column of the value at `path` in `text`, where each element of
+// `path` is a key of a block mapping nested in the previous one
+fn find_path(text: &str, path: &[&str]) -> (Option<usize>, Option<usize>) {
+    let lines = text.lines().collect::<Vec<&str>>();
+    let Some((i, indent)) = locate(&lines, path) else {
+        return (None, None);
     };
+    let key_len = path.last().map_or(0, |k| k.len()) + 1;
+    let rest = &lines[i][indent + key_len..];
+    let column = indent + key_len + rest.len() - rest.trim_start().len() + 1;
+    (Some(i + 1), Some(column))
+}
+
+fn diagnostic(text: &str, path: &[&str], message: String) -> ConfigDiagnostic {
+    let (line, column) = find_path(text, path);
+    ConfigDiagnostic {
+        line,
+        column,
+        message,
+    }
 }
 
-macro_rules! update_boolean_cli_arg {
-    ($cli_arg: expr, $default_arg: expr, $config_arg: expr) => {
-        if $cli_arg.unwrap() == $default_arg.unwrap() {
-            if let Some(x) = $config_arg {
-                *$cli_arg = Some(x);
+// Checks ranges and cross-field constraints of every set value. Returns a
+// (key, message) pair per invalid value.
+pub fn validate_config_values(cfg: &ConfigFFTArgs) -> Vec<(&'static str, String)> {
+    let default = ConfigFFTArgs::default();
+    let mut errors = Vec::new();
+
+    for (key, color) in [
+        ("border_color", &cfg.border_color),
+        ("bar_color", &cfg.bar_color),
+        ("text_color", &cfg.text_color),
+        ("background_color", &cfg.background_color),
+        ("progress_bar_color", &cfg.progress_bar_color),
+    ] {
+        if let Some(c) = color {
+            if parse_color_str(c).is_none() {
+                errors.push((
+                    key,
+                    format!("\"{}\" is not a color like FF0000, #FF000080 or red.", c),
+                ));
             }
         }
+    }
+
+    if cfg.border_size.is_some_and(|v| v < 0) {
+        errors.push(("border_size", String::from("must be 0 or more.")));
+    }
+    if cfg.font_size.is_some_and(|v| v <= 0) {
+        errors.push(("font_size", String::from("must be positive.")));
+    }
+    if cfg.window_width.is_some_and(|v| v <= 0.0) {
+        errors.push(("window_width", String::from("must be positive.")));
+    }
+    if cfg.window_height.is_some_and(|v| v <= 0.0) {
+        errors.push(("window_height", String::from("must be positive.")));
+    }
+    if cfg.progress_bar_thickness.is_some_and(|v| v <= 0.0) {
+        errors.push(("progress_bar_thickness", String::from("must be positive.")));
+    }
+    if cfg.smoothness.is_some_and(|v| v > 3) {
+        errors.push(("smoothness", String::from("must be between 0 and 3 inclusive.")));
+    }
+    if cfg.freq_resolution.is_some_and(|v| !(10..=300).contains(&v)) {
+        errors.push((
+            "freq_resolution",
+            String::from("must be between 10 and 300 inclusive."),
+        ));
+    }
+    if cfg.bins_per_octave.is_some_and(|v| !(1..=48).contains(&v)) {
+        errors.push((
+            "bins_per_octave",
+            String::from("must be between 1 and 48 inclusive."),
+        ));
+    }
+    if cfg.volume.is_some_and(|v| v > 100) {
+        errors.push(("volume", String::from("must be between 0 and 100 inclusive.")));
+    }
+    if cfg.speed.is_some_and(|v| !(MIN_SPEED..=MAX_SPEED).contains(&v)) {
+        errors.push((
+            "speed",
+            format!("must be between {} and {} inclusive.", MIN_SPEED, MAX_SPEED),
+        ));
+    }
+    if cfg.position.as_ref().is_some_and(|p| Placement::parse(p).is_none()) {
+        errors.push((
+            "position",
+            String::from("must be \"center\" or a position like 100,50."),
+        ));
+    }
+    if let Some(a) = &cfg.analyzer {
+        if !analyzer_names().contains(&a.as_str()) {
+            errors.push((
+                "analyzer",
+                format!("must be one of: {}.", analyzer_names().join(", ")),
+            ));
+        }
+    }
+
+    for message in Keybindings::resolve(cfg.keybindings.as_ref()).1 {
+        errors.push(("keybindings", message));
+    }
+
+    // Unset bounds fall back to their defaults
+    let min_freq = cfg.min_freq.or(default.min_freq).unwrap();
+    let max_freq = cfg.max_freq.or(default.max_freq).unwrap();
+    if min_freq < 0.0 {
+        errors.push(("min_freq", String::from("must be 0 or more.")));
+    }
+    if min_freq >= max_freq {
+        let key = match cfg.max_freq {
+            Some(_) => "max_freq",
+            None => "min_freq",
+        };
+        errors.push((
+            key,
+            format!(
+                "min_freq ({}) must be below max_freq ({}).",
+                min_freq, max_freq
+            ),
+        ));
+    }
+
+    errors
+}
+
+// Parses and validates the contents of a config file, collecting every
+// problem instead of stopping at the first one
+pub fn check_config(text: &str) -> ConfigReport {
+    let mut report = ConfigReport {
+        config: None,
+        errors: Vec::new(),
+        warnings: Vec::new(),
     };
+
+    let value = match serde_yaml::from_str::<Value>(text) {
+        Ok(value) => value,
+        Err(e) => {
+            report.errors.push(yaml_diagnostic(&e));
+            return report;
+        }
+    };
+
+    // A file with only comments is an empty config
+    let parsed = match value {
+        Value::Null => serde_yaml::from_str::<ConfigFFTArgs>("{}"),
+        _ => serde_yaml::from_str::<ConfigFFTArgs>(text),
+    };
+    let cfg = match parsed {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            report.errors.push(yaml_diagnostic(&e));
+            return report;
+        }
+    };
+
+    // Unknown keys, at the top level and in each profile
+    let mut known = known_keys();
+    known.push(String::from("profiles"));
+    let mut sections = vec![(Vec::new(), &value)];
+    if let Some(Value::Mapping(profiles)) = value.get("profiles") {
+        for (name, profile) in profiles.iter() {
+            if let Some(name) = name.as_str() {
+                sections.push((vec!["profiles", name], profile));
+            }
+        }
+    }
+    for (section, mapping) in sections {
+        if let Value::Mapping(m) = mapping {
+            for key in m.keys().filter_map(|k| k.as_str()) {
+                let known_in_section = if section.is_empty() {
+                    known.iter().any(|k| k == key)
+                } else {
+                    key == "inherits" || (key != "profiles" && known.iter().any(|k| k == key))
+                };
+                if !known_in_section {
+                    let path = [section.as_slice(), &[key]].concat();
+                    report.warnings.push(diagnostic(
+                        text,
+                        &path,
+                        format!("unknown key \"{}\" is ignored.", path.join(".")),
+                    ));
+                }
+            }
+        }
+    }
+
+    for (key, message) in validate_config_values(&cfg) {
+        report
+            .errors
+            .push(diagnostic(text, &[key], format!("{}: {}", key, message)));
+    }
+    if cfg.inherits.is_some() {
+        report.errors.push(diagnostic(
+            text,
+            &["inherits"],
+            String::from("inherits: only valid inside a profile."),
+        ));
+    }
+
+    // Profiles are checked after inheritance, reporting only the values they set
+    for name in cfg.profile_names() {
+        let name = name.as_str();
+        let resolved = match resolve_profile(&cfg, name) {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                report.errors.push(diagnostic(
+                    text,
+                    &["profiles", name, "inherits"],
+                    format!("profiles.{}: {}", name, e),
+                ));
+                continue;
+            }
+        };
+        let sets_key = |k: &str| find_path(text, &["profiles", name, k]).0.is_some();
+        for (key, message) in validate_config_values(&resolved) {
+            // min_freq < max_freq can be broken by setting either bound
+            let candidates = match key {
+                "min_freq" | "max_freq" => vec![key, "min_freq", "max_freq"],
+                _ => vec![key],
+            };
+            if let Some(at) = candidates.into_iter().find(|k| sets_key(k)) {
+                report.errors.push(diagnostic(
+                    text,
+                    &["profiles", name, at],
+                    format!("profiles.{}.{}: {}", name, key, message),
+                ));
+            }
+        }
+    }
+
+    report.config = Some(cfg);
+    report
+}
+
+fn yaml_diagnostic(e: &serde_yaml::Error) -> ConfigDiagnostic {
+    // serde_yaml appends the location to its message, which is reported separately
+    let message = e.to_string();
+    let message = match (e.location(), message.rfind(" at line ")) {
+        (Some(_), Some(i)) => message[..i].to_string(),
+        _ => message,
+    };
+    ConfigDiagnostic {
+        line: e.location().map(|l| l.line()),
+        column: e.location().map(|l| l.column()),
+        message,
+    }
 }
 
-macro_rules! overwrite_non_default_args {
-    ($user_config_arg: expr, $fft_arg: expr) => {
-        *$user_config_arg = Some($fft_arg);
+pub fn check_config_file(path: &Path) -> Result<ConfigReport> {
+    let text = read_to_string(path).map_err(|source| match source.kind() {
+        io::ErrorKind::NotFound => FftvizError::FileNotFound(path.to_path_buf()),
+        _ => FftvizError::Io {
+            path: path.to_path_buf(),
+            source,
+        },
+    })?;
+    Ok(check_config(&text))
+}
+
+// Reads and validates a config file. Warnings are printed, errors returned.
+pub fn read_config_file(path: &Path) -> Result<ConfigFFTArgs> {
+    let path = path.to_path_buf();
+    let report = check_config_file(&path)?;
+    for warning in &report.warnings {
+        eprintln!("warning: {}: {}", path.display(), warning);
+    }
+
+    match report.config {
+        Some(cfg) if report.errors.is_empty() => Ok(cfg),
+        _ => Err(FftvizError::InvalidConfig {
+            path,
+            diagnostics: report.errors,
+        }),
+    }
+}
+
+// Profiles in the config file at `path`, empty if there is no valid config
+pub fn config_profile_names(path: Option<&Path>) -> Vec<String> {
+    match path {
+        Some(path) if path.is_file() => read_config_file(path)
+            .map(|c| c.profile_names())
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+// Drops unset values so that profiles only list what they override
+fn remove_nulls(value: Value) -> Value {
+    match value {
+        Value::Mapping(m) => Value::Mapping(
+            m.into_iter()
+                .filter(|(_, v)| !v.is_null())
+                .map(|(k, v)| (k, remove_nulls(v)))
+                .collect(),
+        ),
+        v => v,
+    }
+}
+
+// Writes `config` to `cfg_path`, creating its directory if needed. Replaces
+// the whole file, comments included.
+fn write_config(cfg_path: &Path, config: &ConfigFFTArgs) -> Result<()> {
+    let cfg_yaml = serde_yaml::to_value(config)
+        .and_then(|v| serde_yaml::to_string(&remove_nulls(v)))
+        .map_err(|e| FftvizError::ConfigWrite {
+            path: cfg_path.to_path_buf(),
+            source: io::Error::new(io::ErrorKind::InvalidData, e),
+        })?;
+    write_config_text(cfg_path, &cfg_yaml)
+}
+
+fn write_config_text(cfg_path: &Path, text: &str) -> Result<()> {
+    let write_err = |source| FftvizError::ConfigWrite {
+        path: cfg_path.to_path_buf(),
+        source,
     };
+    if let Some(dir) = cfg_path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        create_dir_all(dir).map_err(write_err)?;
+    }
+    write_atomic(cfg_path, text).map_err(write_err)
+}
+
+// Color names accepted in config.yaml and on the command line, lowercase
+// without separators
+const NAMED_COLORS: &[(&str, Color)] = &[
+    ("aliceblue", Color::ALICE_BLUE),
+    ("antiquewhite", Color::ANTIQUE_WHITE),
+    ("aquamarine", Color::AQUAMARINE),
+    ("azure", Color::AZURE),
+    ("beige", Color::BEIGE),
+    ("bisque", Color::BISQUE),
+    ("black", Color::BLACK),
+    ("blue", Color::BLUE),
+    ("crimson", Color::CRIMSON),
+    ("cyan", Color::CYAN),
+    ("darkgray", Color::DARK_GRAY),
+    ("darkgrey", Color::DARK_GRAY),
+    ("darkgreen", Color::DARK_GREEN),
+    ("fuchsia", Color::FUCHSIA),
+    ("magenta", Color::FUCHSIA),
+    ("gold", Color::GOLD),
+    ("gray", Color::GRAY),
+    ("grey", Color::GRAY),
+    ("green", Color::GREEN),
+    ("indigo", Color::INDIGO),
+    ("limegreen", Color::LIME_GREEN),
+    ("maroon", Color::MAROON),
+    ("midnightblue", Color::MIDNIGHT_BLUE),
+    ("navy", Color::NAVY),
+    ("olive", Color::OLIVE),
+    ("orange", Color::ORANGE),
+    ("orangered", Color::ORANGE_RED),
+    ("pink", Color::PINK),
+    ("purple", Color::PURPLE),
+    ("red", Color::RED),
+    ("salmon", Color::SALMON),
+    ("seagreen", Color::SEA_GREEN),
+    ("silver", Color::SILVER),
+    ("teal", Color::TEAL),
+    ("tomato", Color::TOMATO),
+    ("turquoise", Color::TURQUOISE),
+    ("violet", Color::VIOLET),
+    ("white", Color::WHITE),
+    ("yellow", Color::YELLOW),
+    ("yellowgreen", Color::YELLOW_GREEN),
+    ("transparent", Color::NONE),
+];
+
+// Parses a hex color (RGB, RGBA, RRGGBB or RRGGBBAA, optionally starting with
+// #) or a color name like "red" or "Lime Green"
+pub fn parse_color_str(value: &str) -> Option<Color> {
+    if let Ok(color) = Color::hex(value.trim()) {
+        return Some(color);
+    }
+    let name = value
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_ascii_lowercase();
+    NAMED_COLORS
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, color)| *color)
 }
 
+// RRGGBB, or RRGGBBAA when the color is not opaque
 pub fn convert_color_to_hex(c: &Color) -> String {
-    let c_vec = c.rgb_to_vec3();
-    let (r, g, b) = (
-        (c_vec.x * 255.0) as u32,
-        (c_vec.y * 255.0) as u32,
-        (c_vec.z * 255.0) as u32,
-    );
-    format!(
-        "{}{}{}",
-        format!("{:02X}", r),
-        format!("{:02X}", g),
-        format!("{:02X}", b)
-    )
+    let [r, g, b, a] = c.as_rgba_u8();
+    if a == u8::MAX {
+        format!("{:02X}{:02X}{:02X}", r, g, b)
+    } else {
+        format!("{:02X}{:02X}{:02X}{:02X}", r, g, b, a)
+    }
 }
 
-pub fn write_fftargs_to_config(args: &FFTArgs) {
-    let mut default_args = ConfigFFTArgs::default();
-    overwrite_non_default_args!(
-        &mut default_args.background_color,
-        convert_color_to_hex(&args.background_color)
-    );
-    overwrite_non_default_args!(
-        &mut default_args.border_color,
-        convert_color_to_hex(&args.border_color)
-    );
-    overwrite_non_default_args!(
-        &mut default_args.bar_color,
-        convert_color_to_hex(&args.bar_color)
-    );
-    overwrite_non_default_args!(
-        &mut default_args.text_color,
-        convert_color_to_hex(&args.text_color)
-    );
-    overwrite_non_default_args!(&mut default_args.border_size, args.border_size);
-    overwrite_non_default_args!(&mut default_args.display_track_name, args.track_name);
-    overwrite_non_default_args!(&mut default_args.font_size, args.font_size);
-    overwrite_non_default_args!(&mut default_args.smoothness, args.smoothness);
-    overwrite_non_default_args!(&mut default_args.freq_resolution, args.freq_resolution);
-    overwrite_non_default_args!(&mut default_args.window_width, args.window_width);
-    overwrite_non_default_args!(&mut default_args.window_height, args.window_height);
-    overwrite_non_default_args!(&mut default_args.min_freq, args.min_freq);
-    overwrite_non_default_args!(&mut default_args.max_freq, args.max_freq);
-    overwrite_non_default_args!(&mut default_args.volume, args.volume);
-    overwrite_non_default_args!(&mut default_args.title_bar, args.title_bar);
-
-    let cfg_path = config_path();
-    create_dir_all(cfg_path.as_path().parent().unwrap()).unwrap();
-
-    let config_file = OpenOptions::new()
-        .write(true)
-        .create(true)
-        .open(&cfg_path)
-        .expect("Could not open file.");
-    serde_yaml::to_writer(config_file, &default_args).unwrap();
-
-    let mut cfg_yaml: Vec<String> = read_to_string(&cfg_path)
-        .unwrap() 
-        .lines() 
-        .map(String::from)
-        .collect(); 
-
-    cfg_yaml.retain(|x| x.contains(":"));
-
-    remove_file(&cfg_path).unwrap();
-
-    let f = File::create(&cfg_path).expect("Unable to create file");
-    let mut f = BufWriter::new(f);
-    f.write_all(cfg_yaml.join("\n").as_bytes())
-        .expect("Unable to write data");
+// `previous` if it spells the same color as `c` (e.g. a name the user wrote),
+// otherwise `c` in hex
+fn color_to_config(c: &Color, previous: &Option<String>) -> String {
+    let same = |p: &str| parse_color_str(p).map(|p| p.as_rgba_u8()) == Some(c.as_rgba_u8());
+    match previous {
+        Some(p) if same(p) => p.clone(),
+        _ => convert_color_to_hex(c),
+    }
 }
 
-#[allow(dead_code)]
-pub fn reset_config_file() {
-    let default_user_config = ConfigFFTArgs::default();
-
-    let cfg_path = config_path();
-    create_dir_all(cfg_path.as_path().parent().unwrap()).unwrap();
-
-    let config_file = OpenOptions::new()
-        .write(true)
-        .create(true)
-        .open(cfg_path)
-        .expect("Could not open file.");
-    serde_yaml::to_writer(config_file, &default_user_config).unwrap();
-}
-
-pub fn merge_config_with_cli_args(args: &mut CLIArgs, use_default: bool) {
-    let default_user_config = ConfigFFTArgs::default();
-    if !config_exists() {
-        update_cli_arg!(
-            &mut args.background_color,
-            None::<String>,
-            default_user_config.background_color
-        );
-        update_cli_arg!(
-            &mut args.bar_color,
-            None::<String>,
-            default_user_config.bar_color
-        );
-        update_cli_arg!(
-            &mut args.border_color,
-            None::<String>,
-            default_user_config.border_color
-        );
-        update_cli_arg!(
-            &mut args.border_size,
-            None::<i32>,
-            default_user_config.border_size
-        );
-        update_cli_arg!(
-            &mut args.font_size,
-            None::<i32>,
-            default_user_config.font_size
-        );
-        update_cli_arg!(
-            &mut args.freq_resolution,
-            None::<u32>,
-            default_user_config.freq_resolution
-        );
-        update_cli_arg!(
-            &mut args.max_freq,
-            None::<f32>,
-            default_user_config.max_freq
-        );
-        update_cli_arg!(
-            &mut args.min_freq,
-            None::<f32>,
-            default_user_config.min_freq
-        );
-        update_cli_arg!(
-            &mut args.smoothness,
-            None::<u32>,
-            default_user_config.smoothness
-        );
-        update_cli_arg!(
-            &mut args.text_color,
-            None::<String>,
-            default_user_config.text_color
-        );
-        update_cli_arg!(&mut args.volume, None::<u32>, default_user_config.volume);
-        update_cli_arg!(
-            &mut args.window_width,
-            None::<f32>,
-            default_user_config.window_width
-        );
-        update_cli_arg!(
-            &mut args.window_height,
-            None::<f32>,
-            default_user_config.window_height
-        );
-        update_cli_arg!(
-            &mut args.title_bar,
-            None::<bool>,
-            default_user_config.title_bar
-        );
-        return;
+// Every setting of `args` as it would be written to config.yaml. Colors that
+// did not change keep their spelling from `previous`.
+pub fn fftargs_to_config(args: &FFTArgs, previous: &ConfigFFTArgs) -> ConfigFFTArgs {
+    ConfigFFTArgs {
+        border_size: Some(args.border_size),
+        border_color: Some(color_to_config(&args.border_color, &previous.border_color)),
+        bar_color: Some(color_to_config(&args.bar_color, &previous.bar_color)),
+        display_track_name: Some(args.track_name),
+        text_color: Some(color_to_config(&args.text_color, &previous.text_color)),
+        font_size: Some(args.font_size),
+        title_template: Some(args.title_template.clone()),
+        album_art: Some(args.album_art),
+        background_color: Some(color_to_config(
+            &args.background_color,
+            &previous.background_color,
+        )),
+        smoothness: Some(args.smoothness),
+        freq_resolution: Some(args.freq_resolution),
+        analyzer: Some(args.analyzer.clone()),
+        bins_per_octave: Some(args.bins_per_octave),
+        window_width: Some(args.window_width),
+        window_height: Some(args.window_height),
+        min_freq: Some(args.min_freq),
+        max_freq: Some(args.max_freq),
+        display_gui: Some(args.display_gui),
+        vectorscope: Some(args.vectorscope),
+        level_meters: Some(args.level_meters),
+        tuner: Some(args.tuner),
+        piano_axis: Some(args.piano_axis),
+        axis: Some(args.axis),
+        chroma: Some(args.chroma),
+        on_end: Some(args.on_end),
+        progress_bar: Some(args.progress_bar),
+        progress_bar_thickness: Some(args.progress_bar_thickness),
+        progress_bar_color: Some(color_to_config(
+            &args.progress_bar_color,
+            &previous.progress_bar_color,
+        )),
+        time_label: Some(args.time_label),
+        volume: Some(args.volume),
+        speed: Some(args.speed),
+        title_bar: Some(args.title_bar),
+        fullscreen: Some(args.fullscreen),
+        position: Some(args.position.to_string()),
+        monitor: Some(args.monitor),
+        always_on_top: Some(args.always_on_top),
+        transparent: Some(args.transparent),
+        overlay: Some(args.overlay),
+        // Not editable in the app, so kept as written
+        keybindings: previous.keybindings.clone(),
+        inherits: None,
+        profiles: None,
     }
+}
 
-    let user_config_yaml: ConfigFFTArgs;
-    if use_default {
-        user_config_yaml = ConfigFFTArgs::default();
+// Saves the settings changed in the app, i.e. those of `args` that differ
+// from `resolved` (the settings as last loaded), to the config file and
+// returns its path. Other keys are left alone, so values from env vars, the
+// command line or the system config are not pinned into the file. The file
+// is edited in place so comments and key order survive; if that can't be
+// done faithfully (e.g. flow-style YAML), it is rewritten from scratch.
+pub fn write_fftargs_to_config(args: &FFTArgs, resolved: &ConfigFFTArgs) -> Result<PathBuf> {
+    let cfg_path = args.config_path.as_deref().ok_or(FftvizError::NoConfigPath)?;
+    let (text, existing) = if cfg_path.is_file() {
+        let text = read_to_string(cfg_path).map_err(|source| FftvizError::Io {
+            path: cfg_path.to_path_buf(),
+            source,
+        })?;
+        (text, read_config_file(cfg_path)?)
     } else {
-        user_config_yaml = read_config();
-    }
-
-    update_boolean_cli_arg!(&mut args.track_name, default_user_config.display_track_name, user_config_yaml.display_track_name);
-    update_boolean_cli_arg!(&mut args.display_gui, default_user_config.display_gui, user_config_yaml.display_gui);
-    update_boolean_cli_arg!(&mut args.title_bar, default_user_config.title_bar, user_config_yaml.title_bar);
-
-    update_cli_arg!(
-        &mut args.background_color,
-        user_config_yaml.background_color,
-        default_user_config.background_color
-    );
-    update_cli_arg!(
-        &mut args.bar_color,
-        user_config_yaml.bar_color,
-        default_user_config.bar_color
-    );
-    update_cli_arg!(
-        &mut args.border_color,
-        user_config_yaml.border_color,
-        default_user_config.border_color
-    );
-    update_cli_arg!(
-        &mut args.border_size,
-        user_config_yaml.border_size,
-        default_user_config.border_size
-    );
-    update_cli_arg!(
-        &mut args.font_size,
-        user_config_yaml.font_size,
-        default_user_config.font_size
-    );
-    update_cli_arg!(
-        &mut args.freq_resolution,
-        user_config_yaml.freq_resolution,
-        default_user_config.freq_resolution
-    );
-    update_cli_arg!(
-        &mut args.max_freq,
-        user_config_yaml.max_freq,
-        default_user_config.max_freq
-    );
-    update_cli_arg!(
-        &mut args.min_freq,
-        user_config_yaml.min_freq,
-        default_user_config.min_freq
-    );
-    update_cli_arg!(
-        &mut args.smoothness,
-        user_config_yaml.smoothness,
-        default_user_config.smoothness
-    );
-    update_cli_arg!(
-        &mut args.text_color,
-        user_config_yaml.text_color,
-        default_user_config.text_color
-    );
-    update_cli_arg!(
-        &mut args.volume,
-        user_config_yaml.volume,
-        default_user_config.volume
-    );
-    update_cli_arg!(
-        &mut args.window_width,
-        user_config_yaml.window_width,
-        default_user_config.window_width
-    );
-    update_cli_arg!(
-        &mut args.window_height,
-        user_config_yaml.window_height,
-        default_user_config.window_height
-    );
+        (String::new(), ConfigFFTArgs::empty())
+    };
+    let settings = fftargs_to_config(args, resolved).overrides_of(resolved);
+
+    // With a profile active, changes are saved into it. Other profiles are
+    // kept as they are.
+    let (config, prefix) = match &args.profile {
+        None => {
+            let config = ConfigFFTArgs {
+                inherits: existing.inherits.clone(),
+                profiles: existing.profiles.clone(),
+                ..settings.clone().inherit(&existing.base())
+            };
+            (config, Vec::new())
+        }
+        Some(name) => {
+            let current = existing
+                .profiles
+                .as_ref()
+                .and_then(|p| p.get(name))
+                .cloned()
+                .unwrap_or_else(ConfigFFTArgs::empty);
+            let mut config = existing.clone();
+            config.profiles.get_or_insert_with(BTreeMap::new).insert(
+                name.clone(),
+                ConfigFFTArgs {
+                    inherits: current.inherits.clone(),
+                    ..settings.clone().inherit(&current.base())
+                },
+            );
+            (config, vec!["profiles", name.as_str()])
+        }
+    };
+
+    let values = match serde_yaml::to_value(&settings) {
+        Ok(Value::Mapping(m)) => m,
+        _ => Default::default(),
+    };
+    let keys = settings.set_keys();
+    let updates = keys
+        .iter()
+        .filter_map(|key| {
+            let mut path = prefix.clone();
+            path.push(key.as_str());
+            // Mappings (keybindings) are not edited in the app and left as written
+            values
+                .get(key.as_str())
+                .filter(|v| !v.is_mapping())
+                .map(|v| (path, v.clone()))
+        })
+        .collect::<Vec<_>>();
+
+    // Only keep the in-place edit if it reads back as exactly `config`
+    let same_config = |edited: &str| {
+        let intended = serde_yaml::to_value(&config).map(remove_nulls).ok();
+        let parsed = serde_yaml::from_str::<ConfigFFTArgs>(edited)
+            .and_then(serde_yaml::to_value)
+            .map(remove_nulls)
+            .ok();
+        intended.is_some() && intended == parsed
+    };
+    match set_values(&text, &updates) {
+        Some(edited) if same_config(&edited) => write_config_text(cfg_path, &edited)?,
+        _ => write_config(cfg_path, &config)?,
+    }
+    Ok(cfg_path.to_path_buf())
+}
+
+#[allow(dead_code)]
+pub fn reset_config_file(cfg_path: &Path) -> Result<()> {
+    write_config(cfg_path, &ConfigFFTArgs::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invalid_keys(yaml: &str) -> Vec<&'static str> {
+        let cfg = serde_yaml::from_str::<ConfigFFTArgs>(yaml).unwrap();
+        validate_config_values(&cfg).into_iter().map(|(key, _)| key).collect()
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        assert!(validate_config_values(&ConfigFFTArgs::default()).is_empty());
+        assert!(invalid_keys("{}").is_empty());
+    }
+
+    #[test]
+    fn out_of_range_values() {
+        assert_eq!(invalid_keys("smoothness: 4"), ["smoothness"]);
+        assert_eq!(invalid_keys("freq_resolution: 9"), ["freq_resolution"]);
+        assert_eq!(invalid_keys("bins_per_octave: 49"), ["bins_per_octave"]);
+        assert_eq!(invalid_keys("volume: 101"), ["volume"]);
+        assert_eq!(invalid_keys("window_width: 0"), ["window_width"]);
+        assert_eq!(invalid_keys("bar_color: reddish"), ["bar_color"]);
+        assert_eq!(invalid_keys("analyzer: wavelet"), ["analyzer"]);
+        assert!(invalid_keys("smoothness: 3\nfreq_resolution: 300\nvolume: 100").is_empty());
+    }
+
+    #[test]
+    fn frequency_bounds_are_checked_against_each_other_and_defaults() {
+        assert_eq!(invalid_keys("min_freq: 500\nmax_freq: 400"), ["max_freq"]);
+        assert_eq!(invalid_keys("min_freq: 1000000"), ["min_freq"]);
+        assert_eq!(invalid_keys("min_freq: -1"), ["min_freq"]);
+    }
+
+    #[test]
+    fn check_config_reports_locations_and_unknown_keys() {
+        let report = check_config("volume: 50\nsmoothness: 9\ncolour: red\n");
+        assert!(report.config.is_some());
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].line, Some(2));
+        assert_eq!(report.errors[0].column, Some(13));
+        assert_eq!(report.warnings.len(), 1);
+        assert_eq!(report.warnings[0].line, Some(3));
+
+        let report = check_config("volume: [1\n");
+        assert!(report.config.is_none());
+        assert_eq!(report.errors.len(), 1);
+        assert!(check_config("# only comments\n").errors.is_empty());
+    }
+
+    #[test]
+    fn saving_writes_only_settings_changed_in_the_app() {
+        use crate::ArgLayers;
+        use clap::Parser;
+
+        let dir = std::env::temp_dir().join(format!("fftviz-save-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let cfg_path = dir.join("config.yaml");
+        std::fs::write(&cfg_path, "# mine\nbar_color: red\nborder_size: 2\n").unwrap();
+
+        let cli_args = CLIArgs::parse_from([
+            "fftviz",
+            "Cargo.toml",
+            "--config",
+            cfg_path.to_str().unwrap(),
+            "--volume",
+            "30",
+        ]);
+        let mut layers = ArgLayers::new(cli_args).unwrap();
+        let mut args = layers.resolve(None, true).unwrap();
+        args.border_size = 4;
+        write_fftargs_to_config(&args, &layers.resolved).unwrap();
+
+        let text = read_to_string(&cfg_path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(text, "# mine\nbar_color: red\nborder_size: 4\n");
+    }
+
+    #[test]
+    fn system_config_is_the_first_found_in_xdg_config_dirs_then_etc_fftviz() {
+        let dir = std::env::temp_dir().join(format!("fftviz-xdg-{}", std::process::id()));
+        let (first, second) = (dir.join("first"), dir.join("second"));
+        std::fs::create_dir_all(first.join("fftviz")).unwrap();
+        std::fs::create_dir_all(second.join("fftviz")).unwrap();
+        let config = second.join("fftviz").join("config.yaml");
+        std::fs::write(&config, "bar_color: red\n").unwrap();
+        let fallback = dir.join("etc-fftviz.yaml");
+        let missing = dir.join("missing.yaml");
+
+        let dirs = |list: &[&Path]| Some(std::env::join_paths(list).unwrap());
+        let mut found = vec![
+            system_config_path_in(dirs(&[&first, &second]), &missing),
+            system_config_path_in(dirs(&[Path::new("relative"), &second]), &missing),
+            system_config_path_in(dirs(&[&first]), &missing),
+        ];
+        std::fs::write(&fallback, "bar_color: blue\n").unwrap();
+        found.push(system_config_path_in(dirs(&[&first, &second]), &fallback));
+        found.push(system_config_path_in(dirs(&[&first]), &fallback));
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            found,
+            [Some(config.clone()), Some(config.clone()), None, Some(config), Some(fallback)]
+        );
+    }
 }
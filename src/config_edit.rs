@@ -0,0 +1,249 @@
+use serde_yaml::Value;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+
+// Line-based editing of block-style YAML, used to update config.yaml without
+// losing the user's comments, key order and formatting
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+fn is_content(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    !trimmed.is_empty() && !trimmed.starts_with('#')
+}
+
+// Line index and indentation of the key at `path`, where each element of
+// `path` is a key of a block mapping nested in the previous one
+pub fn locate<S: AsRef<str>>(lines: &[S], path: &[&str]) -> Option<(usize, usize)> {
+    let mut start = 0;
+    let mut parent_indent: Option<usize> = None;
+    let mut found = None;
+
+    for key in path {
+        let prefix = format!("{}:", key);
+        found = None;
+        for (i, line) in lines.iter().enumerate().skip(start) {
+            let line = line.as_ref();
+            if !is_content(line) {
+                continue;
+            }
+
+            let indent = indent_of(line);
+            match parent_indent {
+                // Left the parent mapping without finding the key
+                Some(p) if indent <= p => return None,
+                None if indent > 0 => continue,
+                _ => {}
+            }
+            if line.trim_start().starts_with(&prefix) {
+                found = Some((i, indent));
+                parent_indent = Some(indent);
+                start = i + 1;
+                break;
+            }
+        }
+        found?;
+    }
+    found
+}
+
+// Index of the `#` starting a trailing comment in `rest`, ignoring any inside quotes
+fn comment_start(rest: &str) -> Option<usize> {
+    let mut quote = None;
+    let mut prev = ' ';
+    for (i, c) in rest.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '#') if prev.is_whitespace() => return Some(i),
+            _ => {}
+        }
+        prev = c;
+    }
+    None
+}
+
+// Single-line YAML for a scalar value, quoted where needed
+fn render(value: &Value) -> String {
+    serde_yaml::to_string(value)
+        .map(|s| s.trim_end().to_string())
+        .unwrap_or_default()
+}
+
+// Line after the last line belonging to the mapping under the key at `line`
+fn block_end(lines: &[String], line: usize, indent: usize) -> usize {
+    let mut end = line + 1;
+    for (i, l) in lines.iter().enumerate().skip(line + 1) {
+        if !is_content(l) {
+            continue;
+        }
+        if indent_of(l) <= indent {
+            break;
+        }
+        end = i + 1;
+    }
+    end
+}
+
+// Indentation used by the children of the key at `line`
+fn child_indent(lines: &[String], line: usize, indent: usize) -> usize {
+    lines[line + 1..block_end(lines, line, indent)]
+        .iter()
+        .find(|l| is_content(l))
+        .map(|l| indent_of(l))
+        .unwrap_or(indent + 2)
+}
+
+// Inserts `key: value` (or a bare `key:` for a new mapping) into the mapping
+// at `parent`. None if `parent` does not exist.
+fn insert_key(
+    lines: &mut Vec<String>,
+    parent: &[&str],
+    key: &str,
+    value: Option<&Value>,
+) -> Option<()> {
+    let (at, indent) = match parent {
+        // New top-level keys go above the profiles, or at the end
+        [] => (
+            locate(lines, &["profiles"]).map(|(i, _)| i).unwrap_or(lines.len()),
+            0,
+        ),
+        _ => {
+            let (i, indent) = locate(lines, parent)?;
+            (block_end(lines, i, indent), child_indent(lines, i, indent))
+        }
+    };
+
+    let line = match value {
+        Some(v) => format!("{}{}: {}", " ".repeat(indent), key, render(v)),
+        None => format!("{}{}:", " ".repeat(indent), key),
+    };
+    lines.insert(at, line);
+    Some(())
+}
+
+// Sets each `path` to its value in `text`, updating keys in place (keeping
+// trailing comments) and adding missing keys and mappings. The result should
+// be checked by parsing it, since flow-style YAML is not understood.
+pub fn set_values(text: &str, values: &[(Vec<&str>, Value)]) -> Option<String> {
+    let mut lines: Vec<String> = text.lines().map(String::from).collect();
+
+    for (path, value) in values {
+        let (key, parent) = path.split_last()?;
+        match locate(&lines, path) {
+            Some((i, indent)) => {
+                let line = &lines[i];
+                let rest = &line[indent + key.len() + 1..];
+                let comment = comment_start(rest).map(|c| rest[c..].to_string());
+                let mut updated = format!("{}{}: {}", &line[..indent], key, render(value));
+                if let Some(comment) = comment {
+                    updated.push(' ');
+                    updated.push_str(&comment);
+                }
+                lines[i] = updated;
+            }
+            None => {
+                for depth in 1..parent.len() + 1 {
+                    if locate(&lines, &parent[..depth]).is_none() {
+                        insert_key(&mut lines, &parent[..depth - 1], parent[depth - 1], None)?;
+                    }
+                }
+                insert_key(&mut lines, parent, key, Some(value))?;
+            }
+        }
+    }
+
+    let mut edited = lines.join("\n");
+    edited.push('\n');
+    Some(edited)
+}
+
+// Replaces the file at `path` through a temporary file and a rename, so that
+// readers never see a partially written config. Symlinks (e.g. to a dotfiles
+// repo) are followed and the file they point to is replaced.
+pub fn write_atomic(path: &Path, contents: &str) -> io::Result<()> {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp_path = path.with_file_name(format!(".{}.tmp", file_name));
+
+    let result = File::create(&tmp_path)
+        .and_then(|mut f| {
+            f.write_all(contents.as_bytes())?;
+            f.sync_all()
+        })
+        .and_then(|_| fs::rename(&tmp_path, &path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(text: &str, values: &[(Vec<&str>, &str)]) -> String {
+        let values = values
+            .iter()
+            .map(|(path, v)| (path.clone(), serde_yaml::from_str::<Value>(v).unwrap()))
+            .collect::<Vec<_>>();
+        set_values(text, &values).unwrap()
+    }
+
+    #[test]
+    fn updates_in_place_keeping_comments() {
+        let text = "# My settings\nvolume: 50 # not too loud\nbar_color: FF0000\n";
+        let edited = set(text, &[(vec!["volume"], "80"), (vec!["bar_color"], "\"00FF00\"")]);
+        assert_eq!(
+            edited,
+            "# My settings\nvolume: 80 # not too loud\nbar_color: 00FF00\n"
+        );
+    }
+
+    #[test]
+    fn keeps_quoted_hashes_in_values() {
+        let text = "title_template: \"# {title}\" # shown on top\n";
+        let edited = set(text, &[(vec!["title_template"], "\"- {title}\"")]);
+        assert_eq!(edited, "title_template: '- {title}' # shown on top\n");
+    }
+
+    #[test]
+    fn adds_missing_keys_above_profiles() {
+        let text = "volume: 50\nprofiles:\n  quiet:\n    volume: 10\n";
+        let edited = set(text, &[(vec!["smoothness"], "2")]);
+        assert_eq!(
+            edited,
+            "volume: 50\nsmoothness: 2\nprofiles:\n  quiet:\n    volume: 10\n"
+        );
+    }
+
+    #[test]
+    fn adds_nested_keys_and_mappings() {
+        let text = "profiles:\n    quiet:\n        volume: 10\n";
+        let edited = set(
+            text,
+            &[
+                (vec!["profiles", "quiet", "smoothness"], "1"),
+                (vec!["profiles", "loud", "volume"], "100"),
+            ],
+        );
+        let parsed = serde_yaml::from_str::<Value>(&edited).unwrap();
+        let expected = serde_yaml::from_str::<Value>(
+            "profiles: {quiet: {volume: 10, smoothness: 1}, loud: {volume: 100}}",
+        )
+        .unwrap();
+        assert_eq!(parsed, expected);
+        assert!(edited.contains("\n        smoothness: 1\n"));
+    }
+
+    #[test]
+    fn locate_only_matches_keys_of_the_parent() {
+        let lines = ["volume: 1", "profiles:", "  a:", "    volume: 2", "  b:", "    x: 3"];
+        assert_eq!(locate(&lines, &["volume"]), Some((0, 0)));
+        assert_eq!(locate(&lines, &["profiles", "a", "volume"]), Some((3, 4)));
+        assert_eq!(locate(&lines, &["profiles", "b", "volume"]), None);
+    }
+}
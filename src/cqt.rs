@@ -0,0 +1,151 @@
+use crate::analyzer::{Analyzer, BandSpacing};
+use crate::error::Result;
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+// Longest kernel window, also the FFT size used to apply the kernels. Bins whose
+// ideal window is longer get a shortened window (and slightly lower Q).
+const CQT_FFT_SIZE: usize = 8192;
+
+// Lowest frequency analyzed (C1), used when min_freq is below it
+const CQT_MIN_FREQ: f32 = 32.70;
+
+// Spectral kernel values below this fraction of a kernel's peak are dropped
+const KERNEL_THRESHOLD: f32 = 0.0054;
+
+// Sparse spectral kernel for one constant-Q bin
+struct Kernel {
+    freq: f32,
+    weights: Vec<(usize, Complex<f32>)>,
+}
+
+fn hamming(n: usize, len: usize) -> f32 {
+    0.54 - 0.46 * (2.0 * PI * n as f32 / (len - 1).max(1) as f32).cos()
+}
+
+// Spectral kernels for Brown and Puckette's efficient constant-Q transform
+fn cqt_kernels(sample_rate: u32, bins_per_octave: u32, min_freq: f32, max_freq: f32) -> Vec<Kernel> {
+    let sr = sample_rate as f32;
+    let q = 1.0 / (2f32.powf(1.0 / bins_per_octave as f32) - 1.0);
+    let min_freq = min_freq.max(CQT_MIN_FREQ);
+    let max_freq = max_freq.min(sr / 2.0);
+    let n_bins = (bins_per_octave as f32 * (max_freq / min_freq).log2()).ceil().max(1.0) as usize;
+
+    let mut planner = FftPlanner::<f32>::new();
+    let fft = planner.plan_fft_forward(CQT_FFT_SIZE);
+
+    (0..n_bins)
+        .map(|k| {
+            let freq = min_freq * 2f32.powf(k as f32 / bins_per_octave as f32);
+            let len = ((q * sr / freq).ceil() as usize).min(CQT_FFT_SIZE);
+
+            // Windowed complex exponential, centred in the FFT buffer
+            let mut buffer = vec![Complex::new(0.0, 0.0); CQT_FFT_SIZE];
+            let start = (CQT_FFT_SIZE - len) / 2;
+            for n in 0..len {
+                let phase = 2.0 * PI * freq * n as f32 / sr;
+                buffer[start + n] = Complex::from_polar(hamming(n, len) / len as f32, phase);
+            }
+            fft.process(&mut buffer);
+
+            let peak = buffer.iter().fold(0.0f32, |a, b| a.max(b.norm()));
+            let weights = buffer
+                .iter()
+                .enumerate()
+                .filter(|(_, v)| v.norm() > peak * KERNEL_THRESHOLD)
+                .map(|(j, v)| (j, v.conj() / CQT_FFT_SIZE as f32))
+                .collect();
+
+            Kernel { freq, weights }
+        })
+        .collect()
+}
+
+pub struct ConstantQAnalyzer {
+    kernels: Vec<Kernel>,
+    fft: Arc<dyn Fft<f32>>,
+}
+
+impl ConstantQAnalyzer {
+    pub fn new(sample_rate: u32, bins_per_octave: u32, min_freq: f32, max_freq: f32) -> Self {
+        ConstantQAnalyzer {
+            kernels: cqt_kernels(sample_rate, bins_per_octave, min_freq, max_freq),
+            fft: FftPlanner::<f32>::new().plan_fft_forward(CQT_FFT_SIZE),
+        }
+    }
+
+    pub fn band_spacing(bins_per_octave: u32) -> BandSpacing {
+        BandSpacing::Log {
+            ratio: 2f32.powf(1.0 / bins_per_octave as f32),
+            lowest: CQT_MIN_FREQ,
+        }
+    }
+}
+
+impl Analyzer for ConstantQAnalyzer {
+    fn frame_size(&self) -> usize {
+        CQT_FFT_SIZE
+    }
+
+    fn frequencies(&self) -> Vec<f32> {
+        self.kernels.iter().map(|k| k.freq).collect()
+    }
+
+    fn analyze(&mut self, frame: &[f32]) -> Result<Vec<f32>> {
+        let mut buffer = frame
+            .iter()
+            .map(|s| Complex::new(*s, 0.0))
+            .collect::<Vec<Complex<f32>>>();
+        self.fft.process(&mut buffer);
+
+        Ok(self
+            .kernels
+            .iter()
+            .map(|k| {
+                k.weights
+                    .iter()
+                    .map(|(j, w)| buffer[*j] * w)
+                    .sum::<Complex<f32>>()
+                    .norm()
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kernels_are_spaced_by_bins_per_octave() {
+        let kernels = cqt_kernels(44100, 12, 20.0, 1000.0);
+        // min_freq is raised to C1, and the bins cover up to max_freq
+        assert!((kernels[0].freq - CQT_MIN_FREQ).abs() < 1e-3);
+        assert_eq!(kernels.len(), (12.0 * (1000.0f32 / CQT_MIN_FREQ).log2()).ceil() as usize);
+        for pair in kernels.windows(2) {
+            assert!((pair[1].freq / pair[0].freq - 2f32.powf(1.0 / 12.0)).abs() < 1e-4);
+        }
+        assert!(kernels.iter().all(|k| !k.weights.is_empty()));
+    }
+
+    #[test]
+    fn sine_peaks_in_its_own_bin() {
+        let sample_rate = 44100;
+        let frame = (0..CQT_FFT_SIZE)
+            .map(|i| 0.5 * (2.0 * PI * 440.0 * i as f32 / sample_rate as f32).sin())
+            .collect::<Vec<f32>>();
+        let mut analyzer = ConstantQAnalyzer::new(sample_rate, 24, 100.0, 2000.0);
+        let bands = analyzer.analyze(&frame).unwrap();
+        let freqs = analyzer.frequencies();
+
+        let loudest = (0..bands.len())
+            .max_by(|a, b| bands[*a].total_cmp(&bands[*b]))
+            .unwrap();
+        let closest = (0..freqs.len())
+            .min_by(|a, b| (freqs[*a] - 440.0).abs().total_cmp(&(freqs[*b] - 440.0).abs()))
+            .unwrap();
+        assert_eq!(loudest, closest);
+    }
+}
@@ -0,0 +1,86 @@
+use crate::config::ConfigDiagnostic;
+use rodio::decoder::DecoderError;
+use std::io;
+use std::path::PathBuf;
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, FftvizError>;
+
+#[derive(Debug, Error)]
+pub enum FftvizError {
+    #[error("File \"{}\" not found.", .0.display())]
+    FileNotFound(PathBuf),
+
+    #[error("Could not read \"{}\": {source}", .path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("Could not decode \"{}\": {source}. Supported formats are mp3, wav, flac and ogg vorbis.", .path.display())]
+    Decode {
+        path: PathBuf,
+        #[source]
+        source: DecoderError,
+    },
+
+    #[error("\"{}\" does not contain any audio.", .0.display())]
+    EmptyAudio(PathBuf),
+
+    #[error("No audio output device available ({0}). Check that a sound card or output is connected.")]
+    NoOutputDevice(#[from] rodio::StreamError),
+
+    #[error("Could not start playback ({0}).")]
+    Playback(#[from] rodio::PlayError),
+
+    #[error("Invalid config file {}:{}", .path.display(), list_diagnostics(.diagnostics))]
+    InvalidConfig {
+        path: PathBuf,
+        diagnostics: Vec<ConfigDiagnostic>,
+    },
+
+    #[error("Could not write config file {}: {source}", .path.display())]
+    ConfigWrite {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("Could not find a location for config.yaml. Set HOME or XDG_CONFIG_HOME, or pass --config.")]
+    NoConfigPath,
+
+    #[error("Invalid color \"{value}\" for {field}. Expected hex like FF0000 or #FF000080, or a name like red.")]
+    InvalidColor { field: &'static str, value: String },
+
+    #[error("{0}")]
+    InvalidValue(String),
+
+    #[error("Analysis failed: {0}")]
+    Analysis(String),
+}
+
+fn list_diagnostics(diagnostics: &[ConfigDiagnostic]) -> String {
+    diagnostics
+        .iter()
+        .map(|d| format!("\n  {}", d))
+        .collect()
+}
+
+impl FftvizError {
+    // Process exit code, so wrapper scripts can tell failures apart.
+    // 2 is left to clap, which uses it for malformed command lines.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            FftvizError::FileNotFound(_) | FftvizError::Io { .. } => 3,
+            FftvizError::Decode { .. } | FftvizError::EmptyAudio(_) => 4,
+            FftvizError::InvalidConfig { .. }
+            | FftvizError::ConfigWrite { .. }
+            | FftvizError::NoConfigPath
+            | FftvizError::InvalidColor { .. }
+            | FftvizError::InvalidValue(_) => 5,
+            FftvizError::NoOutputDevice(_) | FftvizError::Playback(_) => 6,
+            FftvizError::Analysis(_) => 7,
+        }
+    }
+}
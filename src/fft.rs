@@ -1,26 +1,28 @@
+use crate::analyzer::{Analyzer, BandSpacing};
+use crate::error::{FftvizError, Result};
 use bincode::{deserialize, serialize};
-use rodio::{source::Source, Decoder, OutputStream};
 use serde::{Deserialize, Serialize};
 use spectrum_analyzer::scaling::divide_by_N_sqrt;
 use spectrum_analyzer::windows::hamming_window;
 use spectrum_analyzer::{samples_fft_to_spectrum, FrequencyLimit};
 use std::fs::File;
-use std::io::{self, BufReader, Read, Write};
+use std::io::{self, Read, Write};
 use std::path::PathBuf;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct FFT {
     pub fft: Vec<Vec<f32>>,
+    pub freqs: Vec<f32>,
     pub num_frames: usize,
     pub num_bars: usize,
     pub min: f32,
     pub max: f32,
 }
 
-pub fn time_interpolate(v1: &Vec<f32>, v2: &Vec<f32>, alpha: f32) -> Vec<f32> {
+pub fn time_interpolate(v1: &[f32], v2: &[f32], alpha: f32) -> Vec<f32> {
     v1.iter()
         .zip(v2.iter())
-        .map(|(x, y)| x.clone() * (1.0 - alpha) + y.clone() * alpha)
+        .map(|(x, y)| x * (1.0 - alpha) + y * alpha)
         .collect::<Vec<f32>>()
 }
 
@@ -39,15 +41,24 @@ pub fn space_interpolate(v: &mut Vec<f32>, num_new_frames: u32) {
     }
 }
 
+// Frequency of each displayed bar, following the same mirroring and
+// spatial interpolation that is applied to the magnitudes
+pub fn bar_frequencies(freqs: &[f32], smoothness: u32) -> Vec<f32> {
+    let mut bar_freqs = freqs.to_vec();
+    bar_freqs.reverse();
+    bar_freqs.extend(freqs.iter());
+    space_interpolate(&mut bar_freqs, smoothness);
+    bar_freqs
+}
+
 pub fn smooth_fft(mut fft: FFT, alpha: u32) -> FFT {
     let mut new_fft = Vec::new();
     for i in (alpha as usize)..(fft.num_frames - alpha as usize) {
         let mut new_frame = fft.fft[i].clone();
         new_frame.iter_mut().enumerate().for_each(|(j, x)| {
             *x = ((i - alpha as usize)..(i + alpha as usize))
-                .into_iter()
                 .map(|i| fft.fft[i][j])
-                .sum::<f32>() as f32
+                .sum::<f32>()
                 / (alpha as f32)
         });
         new_fft.push(new_frame);
@@ -56,42 +67,83 @@ pub fn smooth_fft(mut fft: FFT, alpha: u32) -> FFT {
     fft
 }
 
+// Bar value intensity_normalize_fft gives a magnitude already scaled to 0..1
+fn intensity_rescale(x: f32, bounds: &[f32], scaling_factor: &[f32]) -> f32 {
+    for (bound, scale) in bounds.iter().zip(scaling_factor.iter()) {
+        if x < *bound {
+            return x * scale;
+        }
+    }
+    x
+}
+
 pub fn intensity_normalize_fft(mut fft: FFT, bounds: &[f32], scaling_factor: &[f32]) -> FFT {
     let min_max_scale = fft.max - fft.min;
     let rescale = |mut x: Vec<f32>| -> Vec<f32> {
         for i in x.iter_mut() {
-            *i = (*i - fft.min) / min_max_scale;
-            for (bound, scale) in bounds.iter().zip(scaling_factor.iter()) {
-                if *i < *bound {
-                    *i *= scale;
-                    break;
-                }
-            }
+            *i = intensity_rescale((*i - fft.min) / min_max_scale, bounds, scaling_factor);
         }
         x
     };
-    fft.fft = fft.fft.into_iter().map(|x| rescale(x)).collect();
+    fft.fft = fft.fft.into_iter().map(rescale).collect();
     fft
 }
 
+// Converts between band levels in dB, relative to the loudest band of the
+// track, and the bar values normalization turns them into
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LevelScale {
+    // Band magnitudes of the track before smoothing
+    pub min: f32,
+    pub max: f32,
+}
+
+impl LevelScale {
+    pub fn new(fft: &FFT) -> Self {
+        LevelScale {
+            min: fft.min,
+            max: fft.max,
+        }
+    }
+
+    // Level of a band smooth_fft gave the value `smoothed`. smooth_fft sums
+    // 2 * alpha frames and divides by alpha, so its mean is half of that.
+    pub fn db(&self, smoothed: f32) -> f32 {
+        20.0 * (smoothed / 2.0 / self.max).max(1e-6).log10()
+    }
+
+    // Bar value of a band that stays at `db`, before the frequency weighting
+    pub fn bar_value(&self, db: f32, bounds: &[f32], scaling_factor: &[f32]) -> f32 {
+        let smoothed = 2.0 * self.max * 10f32.powf(db / 20.0);
+        intensity_rescale((smoothed - self.min) / (self.max - self.min), bounds, scaling_factor)
+    }
+}
+
+// Level in dB of every bar in every frame, laid out like the bars
+#[derive(Debug, Clone, Default)]
+pub struct BarLevels {
+    pub db: Vec<Vec<f32>>,
+    pub scale: LevelScale,
+}
+
 pub fn frequency_normalize_fft(mut fft: FFT, scaling_factor: &[f32]) -> FFT {
     let n_freq_buckets = scaling_factor.len();
     let n_bars = fft.fft[0].len();
-    let bars_per_bucket = n_bars / n_freq_buckets;
+    let bars_per_bucket = (n_bars / n_freq_buckets).max(1);
     let rescale = |mut x: Vec<f32>| -> Vec<f32> {
         for (i, v) in x.iter_mut().enumerate() {
             *v *= scaling_factor[(i / bars_per_bucket).min(n_freq_buckets - 1)];
         }
         x
     };
-    fft.fft = fft.fft.into_iter().map(|x| rescale(x)).collect();
+    fft.fft = fft.fft.into_iter().map(rescale).collect();
     fft
 }
 
 #[allow(dead_code)]
 pub fn write_fft_to_binary_file(filepath: &PathBuf, fft: &FFT) -> io::Result<()> {
     let mut file = File::create(filepath)?;
-    let encoded_data = serialize(fft).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    let encoded_data = serialize(fft).map_err(io::Error::other)?;
     file.write_all(&encoded_data)?;
     Ok(())
 }
@@ -101,82 +153,96 @@ pub fn read_fft_from_binary_file(filepath: &PathBuf) -> io::Result<FFT> {
     let mut file = File::open(filepath)?;
     let mut buffer = Vec::new();
     file.read_to_end(&mut buffer)?;
-    let fft: FFT = deserialize(&buffer).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    let fft: FFT = deserialize(&buffer).map_err(io::Error::other)?;
     Ok(fft)
 }
 
-pub fn compute_fft(
-    audio_path: &PathBuf,
-    fft_fps: u32,
-    freq_res: u32,
+// Gain applied to the channel-averaged samples, relative to i16 full scale
+const SPECTRUM_INPUT_GAIN: f32 = 20.0 * i16::MAX as f32;
+
+// Linear FFT analyzer backed by spectrum_analyzer. An FFT window of
+// `window_size` samples holds `window_size / n_channels` frames, zero padded.
+pub struct SpectrumAnalyzer {
+    sample_rate: u32,
+    window_size: usize,
+    frame_size: usize,
     min_freq: f32,
     max_freq: f32,
-) -> FFT {
-    let fft_window = ((256 as u64 / 107 as u64) * freq_res as u64).next_power_of_two() as i32;
-    let (_stream, _) = OutputStream::try_default().unwrap();
-    let file = BufReader::new(File::open(audio_path).unwrap());
-    let source = Decoder::new(file).unwrap();
-
-    let n_channels = source.channels() as i32;
-    let sample_rate = source.sample_rate() as i32;
-
-    let mut source = source.peekable();
-
-    let step_amount = ((sample_rate * n_channels) as usize / fft_fps as usize) as i32 - fft_window;
-    let (mut min, mut max): (f32, f32) = (100.0, 0.0);
-    let mut output_vec = Vec::new();
-
-    while source.peek().is_some() {
-        let mut frame = Vec::new();
-        for _ in 0..fft_window {
-            match source.next() {
-                Some(x) => frame.push(x),
-                None => {}
-            };
-        }
+    freqs: Vec<f32>,
+}
 
-        for _ in 0..step_amount {
-            source.next();
-        }
+impl SpectrumAnalyzer {
+    pub fn new(
+        sample_rate: u32,
+        n_channels: usize,
+        freq_res: u32,
+        min_freq: f32,
+        max_freq: f32,
+    ) -> Result<Self> {
+        let window_size = Self::window_size(freq_res);
+        let mut analyzer = SpectrumAnalyzer {
+            sample_rate,
+            window_size,
+            frame_size: window_size / n_channels.max(1),
+            min_freq,
+            max_freq,
+            freqs: Vec::new(),
+        };
+        analyzer.freqs = analyzer
+            .spectrum(&vec![0.0; window_size])?
+            .into_iter()
+            .map(|(fr, _)| fr)
+            .collect();
+        Ok(analyzer)
+    }
 
-        let mut samples = vec![0.0; fft_window as usize];
-        for (i, stereo) in frame.chunks(n_channels as usize).enumerate() {
-            samples[i] = stereo
-                .iter()
-                .map(|x| x.clone() as f32 * 20.0 / n_channels as f32)
-                .sum::<f32>();
-        }
+    fn window_size(freq_res: u32) -> usize {
+        ((256_u64 / 107) * freq_res as u64).next_power_of_two() as usize
+    }
+
+    // FFT bins are sample_rate / window_size apart
+    pub fn band_spacing(sample_rate: u32, freq_res: u32) -> BandSpacing {
+        BandSpacing::Linear(sample_rate as f32 / Self::window_size(freq_res) as f32)
+    }
 
+    fn spectrum(&self, frame: &[f32]) -> Result<Vec<(f32, f32)>> {
+        let mut samples = frame
+            .iter()
+            .map(|x| x * SPECTRUM_INPUT_GAIN)
+            .collect::<Vec<f32>>();
+        samples.resize(self.window_size, 0.0);
         let hann_window = hamming_window(&samples);
 
         let spectrum_hann_window = samples_fft_to_spectrum(
             &hann_window,
-            sample_rate as u32,
-            FrequencyLimit::Range(min_freq, max_freq),
+            self.sample_rate,
+            FrequencyLimit::Range(self.min_freq, self.max_freq),
             Some(&divide_by_N_sqrt),
         )
-        .unwrap();
+        .map_err(|e| FftvizError::Analysis(format!("{:?}", e)))?;
 
-        let curr_vec = spectrum_hann_window
+        Ok(spectrum_hann_window
             .data()
-            .into_iter()
-            .map(|(_, fval)| fval.val())
-            .collect::<Vec<f32>>();
+            .iter()
+            .map(|(fr, fval)| (fr.val(), fval.val()))
+            .collect())
+    }
+}
 
-        for val in curr_vec.iter() {
-            max = max.max(*val);
-            min = min.min(*val);
-        }
-        output_vec.push(curr_vec.clone());
+impl Analyzer for SpectrumAnalyzer {
+    fn frame_size(&self) -> usize {
+        self.frame_size
+    }
+
+    fn frequencies(&self) -> Vec<f32> {
+        self.freqs.clone()
     }
 
-    let num_frames = output_vec.len();
-    let num_bars = output_vec[0].len();
-    FFT {
-        fft: output_vec,
-        num_frames,
-        num_bars,
-        min,
-        max,
+    fn analyze(&mut self, frame: &[f32]) -> Result<Vec<f32>> {
+        Ok(self
+            .spectrum(frame)?
+            .into_iter()
+            .map(|(_, fval)| fval)
+            .collect())
     }
 }
@@ -0,0 +1,545 @@
+use bevy::prelude::{ButtonInput, KeyCode};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    ToggleGui,
+    ToggleHelp,
+    PlayPause,
+    VolumeUp,
+    VolumeDown,
+    SeekForward,
+    SeekBackward,
+    SpeedUp,
+    SpeedDown,
+    SpeedReset,
+    NextTrack,
+    SetLoopStart,
+    SetLoopEnd,
+    ClearLoop,
+    ToggleVectorscope,
+    ToggleLevelMeters,
+    ToggleTuner,
+    TogglePianoAxis,
+    ToggleAxis,
+    CycleChroma,
+    CycleLayout,
+    ToggleFullscreen,
+    Screenshot,
+}
+
+pub struct ActionInfo {
+    pub action: Action,
+    // Key in the config's keybindings section
+    pub name: &'static str,
+    pub description: &'static str,
+    pub default_keys: &'static [&'static str],
+}
+
+// Every action that can be bound, in the order the help overlay lists them
+pub const ACTIONS: &[ActionInfo] = &[
+    ActionInfo {
+        action: Action::Quit,
+        name: "quit",
+        description: "Quit",
+        default_keys: &["q"],
+    },
+    ActionInfo {
+        action: Action::ToggleGui,
+        name: "toggle_gui",
+        description: "Open or close the config gui",
+        default_keys: &["e"],
+    },
+    ActionInfo {
+        action: Action::ToggleHelp,
+        name: "toggle_help",
+        description: "Show or hide this list",
+        default_keys: &["?"],
+    },
+    ActionInfo {
+        action: Action::PlayPause,
+        name: "play_pause",
+        description: "Pause or resume playback",
+        default_keys: &["space"],
+    },
+    ActionInfo {
+        action: Action::VolumeUp,
+        name: "volume_up",
+        description: "Increase volume",
+        default_keys: &["up"],
+    },
+    ActionInfo {
+        action: Action::VolumeDown,
+        name: "volume_down",
+        description: "Decrease volume",
+        default_keys: &["down"],
+    },
+    ActionInfo {
+        action: Action::SeekForward,
+        name: "seek_forward",
+        description: "Skip ahead 5 seconds",
+        default_keys: &["right"],
+    },
+    ActionInfo {
+        action: Action::SeekBackward,
+        name: "seek_backward",
+        description: "Go back 5 seconds",
+        default_keys: &["left"],
+    },
+    ActionInfo {
+        action: Action::SpeedUp,
+        name: "speed_up",
+        description: "Play faster",
+        default_keys: &["]"],
+    },
+    ActionInfo {
+        action: Action::SpeedDown,
+        name: "speed_down",
+        description: "Play slower",
+        default_keys: &["["],
+    },
+    ActionInfo {
+        action: Action::SpeedReset,
+        name: "speed_reset",
+        description: "Play at normal speed",
+        default_keys: &["backspace"],
+    },
+    ActionInfo {
+        action: Action::NextTrack,
+        name: "next_track",
+        description: "Skip to the next file",
+        default_keys: &["n"],
+    },
+    ActionInfo {
+        action: Action::SetLoopStart,
+        name: "set_loop_start",
+        description: "Set the loop start (A) here",
+        default_keys: &["a"],
+    },
+    ActionInfo {
+        action: Action::SetLoopEnd,
+        name: "set_loop_end",
+        description: "Set the loop end (B) here and start looping",
+        default_keys: &["b"],
+    },
+    ActionInfo {
+        action: Action::ClearLoop,
+        name: "clear_loop",
+        description: "Stop looping",
+        default_keys: &["shift+a"],
+    },
+    ActionInfo {
+        action: Action::ToggleVectorscope,
+        name: "toggle_vectorscope",
+        description: "Toggle the stereo vectorscope",
+        default_keys: &["v"],
+    },
+    ActionInfo {
+        action: Action::ToggleLevelMeters,
+        name: "toggle_level_meters",
+        description: "Toggle the level meters",
+        default_keys: &["l"],
+    },
+    ActionInfo {
+        action: Action::ToggleTuner,
+        name: "toggle_tuner",
+        description: "Toggle tuner mode",
+        default_keys: &["t"],
+    },
+    ActionInfo {
+        action: Action::TogglePianoAxis,
+        name: "toggle_piano_axis",
+        description: "Toggle the piano keyboard axis",
+        default_keys: &["p"],
+    },
+    ActionInfo {
+        action: Action::ToggleAxis,
+        name: "toggle_axis",
+        description: "Toggle frequency labels and dB gridlines",
+        default_keys: &["g"],
+    },
+    ActionInfo {
+        action: Action::CycleChroma,
+        name: "cycle_chroma",
+        description: "Cycle the chromagram view",
+        default_keys: &["c"],
+    },
+    ActionInfo {
+        action: Action::CycleLayout,
+        name: "cycle_layout",
+        description: "Cycle panel layouts",
+        default_keys: &["tab"],
+    },
+    ActionInfo {
+        action: Action::ToggleFullscreen,
+        name: "toggle_fullscreen",
+        description: "Toggle fullscreen",
+        default_keys: &["f"],
+    },
+    ActionInfo {
+        action: Action::Screenshot,
+        name: "screenshot",
+        description: "Save a screenshot",
+        default_keys: &["s"],
+    },
+];
+
+pub fn action_names() -> Vec<&'static str> {
+    ACTIONS.iter().map(|a| a.name).collect()
+}
+
+// Keys bound to an action in config.yaml: one chord, or a list of them.
+// An empty list unbinds the action.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum KeyList {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl KeyList {
+    pub fn chords(&self) -> Vec<&str> {
+        match self {
+            KeyList::One(chord) => vec![chord.as_str()],
+            KeyList::Many(chords) => chords.iter().map(String::as_str).collect(),
+        }
+    }
+}
+
+// A key together with the modifiers that have to be held, e.g. "ctrl+shift+s"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyChord {
+    pub key: KeyCode,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+    pub super_key: bool,
+}
+
+// Key names accepted in chords, and the name each key is displayed with
+const KEY_NAMES: &[(&str, KeyCode)] = &[
+    ("space", KeyCode::Space),
+    ("enter", KeyCode::Enter),
+    ("return", KeyCode::Enter),
+    ("tab", KeyCode::Tab),
+    ("escape", KeyCode::Escape),
+    ("esc", KeyCode::Escape),
+    ("backspace", KeyCode::Backspace),
+    ("delete", KeyCode::Delete),
+    ("insert", KeyCode::Insert),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("pageup", KeyCode::PageUp),
+    ("pagedown", KeyCode::PageDown),
+    ("up", KeyCode::ArrowUp),
+    ("down", KeyCode::ArrowDown),
+    ("left", KeyCode::ArrowLeft),
+    ("right", KeyCode::ArrowRight),
+    ("-", KeyCode::Minus),
+    ("=", KeyCode::Equal),
+    (",", KeyCode::Comma),
+    (".", KeyCode::Period),
+    ("/", KeyCode::Slash),
+    ("\\", KeyCode::Backslash),
+    (";", KeyCode::Semicolon),
+    ("'", KeyCode::Quote),
+    ("`", KeyCode::Backquote),
+    ("[", KeyCode::BracketLeft),
+    ("]", KeyCode::BracketRight),
+    ("a", KeyCode::KeyA),
+    ("b", KeyCode::KeyB),
+    ("c", KeyCode::KeyC),
+    ("d", KeyCode::KeyD),
+    ("e", KeyCode::KeyE),
+    ("f", KeyCode::KeyF),
+    ("g", KeyCode::KeyG),
+    ("h", KeyCode::KeyH),
+    ("i", KeyCode::KeyI),
+    ("j", KeyCode::KeyJ),
+    ("k", KeyCode::KeyK),
+    ("l", KeyCode::KeyL),
+    ("m", KeyCode::KeyM),
+    ("n", KeyCode::KeyN),
+    ("o", KeyCode::KeyO),
+    ("p", KeyCode::KeyP),
+    ("q", KeyCode::KeyQ),
+    ("r", KeyCode::KeyR),
+    ("s", KeyCode::KeyS),
+    ("t", KeyCode::KeyT),
+    ("u", KeyCode::KeyU),
+    ("v", KeyCode::KeyV),
+    ("w", KeyCode::KeyW),
+    ("x", KeyCode::KeyX),
+    ("y", KeyCode::KeyY),
+    ("z", KeyCode::KeyZ),
+    ("0", KeyCode::Digit0),
+    ("1", KeyCode::Digit1),
+    ("2", KeyCode::Digit2),
+    ("3", KeyCode::Digit3),
+    ("4", KeyCode::Digit4),
+    ("5", KeyCode::Digit5),
+    ("6", KeyCode::Digit6),
+    ("7", KeyCode::Digit7),
+    ("8", KeyCode::Digit8),
+    ("9", KeyCode::Digit9),
+    ("f1", KeyCode::F1),
+    ("f2", KeyCode::F2),
+    ("f3", KeyCode::F3),
+    ("f4", KeyCode::F4),
+    ("f5", KeyCode::F5),
+    ("f6", KeyCode::F6),
+    ("f7", KeyCode::F7),
+    ("f8", KeyCode::F8),
+    ("f9", KeyCode::F9),
+    ("f10", KeyCode::F10),
+    ("f11", KeyCode::F11),
+    ("f12", KeyCode::F12),
+];
+
+impl KeyChord {
+    // Parses chords like "q", "Ctrl+Shift+S" or "?" (shift+/). Names are case-insensitive.
+    pub fn parse(chord: &str) -> Option<KeyChord> {
+        let chord = chord.trim().to_lowercase();
+        let (modifiers, key) = match chord.rsplit_once('+') {
+            Some((modifiers, key)) => (modifiers.split('+').collect::<Vec<&str>>(), key),
+            None => (Vec::new(), chord.as_str()),
+        };
+
+        let mut parsed = match key.trim() {
+            "?" => KeyChord::new(KeyCode::Slash).with_shift(),
+            key => KeyChord::new(
+                KEY_NAMES.iter().find(|(name, _)| *name == key).map(|(_, k)| *k)?,
+            ),
+        };
+        for modifier in modifiers {
+            match modifier.trim() {
+                "ctrl" | "control" => parsed.ctrl = true,
+                "shift" => parsed.shift = true,
+                "alt" | "option" => parsed.alt = true,
+                "super" | "cmd" | "meta" | "win" => parsed.super_key = true,
+                _ => return None,
+            }
+        }
+        Some(parsed)
+    }
+
+    fn new(key: KeyCode) -> KeyChord {
+        KeyChord {
+            key,
+            ctrl: false,
+            shift: false,
+            alt: false,
+            super_key: false,
+        }
+    }
+
+    fn with_shift(mut self) -> KeyChord {
+        self.shift = true;
+        self
+    }
+
+    // Whether the key was just pressed with exactly these modifiers held
+    pub fn just_pressed(&self, input: &ButtonInput<KeyCode>) -> bool {
+        let held = |left, right| input.any_pressed([left, right]);
+        input.just_pressed(self.key)
+            && self.ctrl == held(KeyCode::ControlLeft, KeyCode::ControlRight)
+            && self.shift == held(KeyCode::ShiftLeft, KeyCode::ShiftRight)
+            && self.alt == held(KeyCode::AltLeft, KeyCode::AltRight)
+            && self.super_key == held(KeyCode::SuperLeft, KeyCode::SuperRight)
+    }
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.key == KeyCode::Slash && self.shift && !(self.ctrl || self.alt || self.super_key) {
+            return write!(f, "?");
+        }
+        for (held, name) in [
+            (self.ctrl, "Ctrl+"),
+            (self.shift, "Shift+"),
+            (self.alt, "Alt+"),
+            (self.super_key, "Super+"),
+        ] {
+            if held {
+                write!(f, "{}", name)?;
+            }
+        }
+        match KEY_NAMES.iter().find(|(_, k)| *k == self.key) {
+            Some((name, _)) if name.len() == 1 => write!(f, "{}", name.to_uppercase()),
+            Some((name, _)) => {
+                let mut chars = name.chars();
+                let first = chars.next().unwrap_or_default().to_uppercase();
+                write!(f, "{}{}", first, chars.as_str())
+            }
+            None => write!(f, "{:?}", self.key),
+        }
+    }
+}
+
+// Keys bound to each action, in the order of ACTIONS
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keybindings {
+    pub bindings: Vec<(Action, Vec<KeyChord>)>,
+}
+
+impl Default for Keybindings {
+    fn default() -> Self {
+        Keybindings::resolve(None).0
+    }
+}
+
+impl Keybindings {
+    // Bindings from config.yaml's keybindings section over the defaults, and a
+    // message for each unknown action, unparsable chord or chord bound twice
+    pub fn resolve(config: Option<&BTreeMap<String, KeyList>>) -> (Keybindings, Vec<String>) {
+        let mut errors = Vec::new();
+
+        for name in config.into_iter().flat_map(|c| c.keys()) {
+            if !action_names().contains(&name.as_str()) {
+                errors.push(format!(
+                    "unknown action \"{}\". Actions: {}.",
+                    name,
+                    action_names().join(", ")
+                ));
+            }
+        }
+
+        let mut bindings = Vec::new();
+        for info in ACTIONS {
+            let chords = match config.and_then(|c| c.get(info.name)) {
+                Some(keys) => keys.chords(),
+                None => info.default_keys.to_vec(),
+            };
+            let mut parsed = Vec::new();
+            for chord in chords {
+                match KeyChord::parse(chord) {
+                    Some(c) => parsed.push(c),
+                    None => errors.push(format!(
+                        "{}: \"{}\" is not a key like q, space, f5 or ctrl+shift+s.",
+                        info.name, chord
+                    )),
+                }
+            }
+            bindings.push((info.action, parsed));
+        }
+
+        // Every chord may trigger only one action
+        let mut seen: Vec<(KeyChord, &str)> = Vec::new();
+        for ((_, chords), info) in bindings.iter().zip(ACTIONS) {
+            for chord in chords {
+                match seen.iter().find(|(c, _)| c == chord) {
+                    Some((_, other)) if *other != info.name => errors.push(format!(
+                        "{} is bound to both {} and {}. Rebind or unbind (with []) one of them.",
+                        chord, other, info.name
+                    )),
+                    Some(_) => {}
+                    None => seen.push((*chord, info.name)),
+                }
+            }
+        }
+
+        (Keybindings { bindings }, errors)
+    }
+
+    pub fn keys(&self, action: Action) -> &[KeyChord] {
+        self.bindings
+            .iter()
+            .find(|(a, _)| *a == action)
+            .map(|(_, keys)| keys.as_slice())
+            .unwrap_or_default()
+    }
+
+    pub fn just_pressed(&self, action: Action, input: &ButtonInput<KeyCode>) -> bool {
+        self.keys(action).iter().any(|k| k.just_pressed(input))
+    }
+
+    // Keys bound to `action`, e.g. "Q, Ctrl+Q"
+    pub fn describe(&self, action: Action) -> String {
+        match self.keys(action) {
+            [] => String::from("unbound"),
+            keys => keys
+                .iter()
+                .map(|k| k.to_string())
+                .collect::<Vec<String>>()
+                .join(", "),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(entries: &[(&str, KeyList)]) -> BTreeMap<String, KeyList> {
+        entries
+            .iter()
+            .map(|(name, keys)| (name.to_string(), keys.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_chords() {
+        assert_eq!(KeyChord::parse("q"), Some(KeyChord::new(KeyCode::KeyQ)));
+        assert_eq!(KeyChord::parse(" Space "), Some(KeyChord::new(KeyCode::Space)));
+        assert_eq!(
+            KeyChord::parse("?"),
+            Some(KeyChord::new(KeyCode::Slash).with_shift())
+        );
+
+        let chord = KeyChord::parse("Ctrl+Shift+S").unwrap();
+        assert_eq!(chord.key, KeyCode::KeyS);
+        assert!(chord.ctrl && chord.shift && !chord.alt && !chord.super_key);
+        assert_eq!(KeyChord::parse("cmd+="), KeyChord::parse("super+="));
+    }
+
+    #[test]
+    fn rejects_unknown_keys_and_modifiers() {
+        assert_eq!(KeyChord::parse("hyper+q"), None);
+        assert_eq!(KeyChord::parse("f13"), None);
+        assert_eq!(KeyChord::parse(""), None);
+    }
+
+    #[test]
+    fn displays_chords_the_way_they_parse() {
+        for chord in ["Q", "Space", "?", "Ctrl+Shift+S", "Alt+F5", "Up"] {
+            assert_eq!(KeyChord::parse(chord).unwrap().to_string(), chord);
+        }
+    }
+
+    #[test]
+    fn defaults_have_no_conflicts() {
+        let (bindings, errors) = Keybindings::resolve(None);
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(bindings.bindings.len(), ACTIONS.len());
+        assert_eq!(bindings.describe(Action::Quit), "Q");
+    }
+
+    #[test]
+    fn config_overrides_and_unbinds() {
+        let cfg = config(&[
+            ("quit", KeyList::Many(vec!["q".into(), "ctrl+q".into()])),
+            ("screenshot", KeyList::Many(Vec::new())),
+        ]);
+        let (bindings, errors) = Keybindings::resolve(Some(&cfg));
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(bindings.describe(Action::Quit), "Q, Ctrl+Q");
+        assert_eq!(bindings.describe(Action::Screenshot), "unbound");
+    }
+
+    #[test]
+    fn reports_conflicts_unknown_actions_and_bad_chords() {
+        let cfg = config(&[
+            ("toggle_tuner", KeyList::One("q".into())),
+            ("fly", KeyList::One("x".into())),
+            ("toggle_axis", KeyList::One("ctrl+nope".into())),
+        ]);
+        let (_, errors) = Keybindings::resolve(Some(&cfg));
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+        assert!(errors.iter().any(|e| e.contains("unknown action \"fly\"")));
+        assert!(errors.iter().any(|e| e.starts_with("toggle_axis:")));
+        assert!(errors.iter().any(|e| e.contains("bound to both quit and toggle_tuner")));
+    }
+}
@@ -0,0 +1,651 @@
+
+pub mod analyzer;
+pub mod args;
+pub mod audio;
+pub mod chroma;
+pub mod config;
+pub mod config_edit;
+pub mod cqt;
+pub mod error;
+pub mod fft;
+pub mod keybindings;
+pub mod loudness;
+pub mod metadata;
+pub mod pitch;
+pub mod playback;
+pub mod settings;
+pub mod systems;
+pub mod window;
+
+pub use analyzer::*;
+pub use args::*;
+pub use audio::*;
+pub use chroma::*;
+pub use config::*;
+pub use error::*;
+pub use fft::*;
+pub use keybindings::*;
+pub use loudness::*;
+pub use metadata::*;
+pub use pitch::*;
+pub use playback::*;
+pub use settings::*;
+pub use window::*;
+use systems::egui::*;
+use systems::get_keyboard_input::*;
+use systems::get_mouse_input::*;
+use systems::help_overlay::*;
+use systems::recompute_fft::*;
+use systems::startup::*;
+use systems::update_ab_loop::*;
+use systems::update_axis::*;
+use systems::update_chroma::*;
+use systems::update_fft::*;
+use systems::update_frame_counters::*;
+use systems::update_level_meters::*;
+use systems::update_piano_axis::*;
+use systems::update_time_label::*;
+use systems::update_seek_bar::*;
+use systems::update_track_end::*;
+use systems::update_track_info::*;
+use systems::update_tuner::*;
+use systems::update_vectorscope::*;
+use systems::update_view_settings::*;
+use systems::watch_config::*;
+
+use bevy::prelude::*;
+use bevy_egui::EguiPlugin;
+use rodio::OutputStreamHandle;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Instant;
+
+// Timing related constants
+const RENDERING_FPS: u32 = 60;
+const TIME_BETWEEN_FRAMES: f64 = 1.0 / RENDERING_FPS as f64;
+const FFT_FPS: u32 = 12;
+const TIME_BETWEEN_FFT_FRAMES: f64 = 1.0 / FFT_FPS as f64;
+
+// Playback control constants
+const SEEK_STEP: f64 = 5.0;
+const VOLUME_STEP: u32 = 5;
+const DOUBLE_CLICK_TIME: f64 = 0.3;
+const SCROLL_PIXELS_PER_LINE: f32 = 50.0;
+pub const MIN_SPEED: f32 = 0.5;
+pub const MAX_SPEED: f32 = 2.0;
+const SPEED_STEP: f32 = 0.05;
+// Seconds the bars take to fade out at the end of a track
+const FADE_OUT_TIME: f64 = 1.0;
+
+// Seek bar size, and height of the area along the bottom edge that shows it
+const SEEK_BAR_HOVER_HEIGHT: f32 = 24.0;
+
+// Normalization constants
+const AVERAGING_WINDOW: u32 = 1;
+const RESCALING_THRESHOLDS: &[f32] = &[0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8, 0.9];
+const INTENSITY_RESCALING: &[f32] = &[0.4, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.6, 0.5];
+const FREQ_RESCALING: &[f32] = &[0.9, 1.2, 1.2, 1.2, 1.0];
+// Each FREQ_RESCALING bucket needs at least one band
+const MIN_BANDS: usize = FREQ_RESCALING.len();
+
+// Bar height clamps
+const MIN_BAR_HEIGHT: f32 = 0.001;
+const MAX_BAR_HEIGHT: f32 = 0.45;
+
+// Vectorscope constants
+const VECTORSCOPE_SIZE: f32 = 0.3;
+const VECTORSCOPE_WINDOW: f64 = 0.05;
+
+// Level meter constants
+const LEVEL_METER_WIDTH: f32 = 8.0;
+const LEVEL_METER_GAP: f32 = 4.0;
+const LEVEL_METER_HEIGHT: f32 = 0.4;
+const LEVEL_METER_FLOOR: f32 = -60.0;
+const PEAK_HOLD_TIME: f64 = 2.0;
+const CLIP_LEVEL: f32 = -0.1;
+
+// Tuner constants
+const TUNER_MIN_FREQ: f32 = 40.0;
+const TUNER_MAX_FREQ: f32 = 2000.0;
+const TUNER_IN_TUNE_CENTS: f32 = 5.0;
+const PIANO_AXIS_HEIGHT: f32 = 16.0;
+
+// Frequency axis constants
+const AXIS_TICKS: &[f32] = &[50.0, 100.0, 200.0, 500.0, 1000.0, 2000.0, 5000.0, 10000.0, 20000.0];
+// Gridlines, in dB below the loudest band of the track
+const AXIS_GRID_DB: &[f32] = &[-6.0, -12.0, -18.0, -24.0, -30.0];
+
+// Chromagram panel size, as a fraction of the smaller window dimension
+const CHROMA_SIZE: f32 = 0.3;
+
+// Album art size, as a fraction of the smaller window dimension, and the
+// brightness the blurred background cover is dimmed to
+const ALBUM_ART_SIZE: f32 = 0.25;
+const ALBUM_ART_DIMMING: f32 = 0.4;
+
+// Visualizer settings, resolved from defaults, config files, env vars and CLI args
+#[derive(Resource, Clone, Component, Debug, PartialEq)]
+pub struct FFTArgs {
+    // First track, and every track given on the command line
+    pub file_path: PathBuf,
+    pub playlist: Vec<PathBuf>,
+    pub config_path: Option<PathBuf>,
+    pub profile: Option<String>,
+    // Part of the track to play and analyze, in seconds. Playback positions
+    // are relative to `start`.
+    pub start: f64,
+    pub end: Option<f64>,
+    pub border_size: i32,
+    pub border_color: Color,
+    pub bar_color: Color,
+    pub track_name: bool,
+    pub text_color: Color,
+    pub font_size: i32,
+    pub title_template: String,
+    pub album_art: AlbumArt,
+    pub background_color: Color,
+    pub smoothness: u32,
+    pub freq_resolution: u32,
+    pub window_width: f32,
+    pub window_height: f32,
+    pub min_freq: f32,
+    pub max_freq: f32,
+    pub display_gui: bool,
+    pub vectorscope: bool,
+    pub level_meters: bool,
+    pub tuner: bool,
+    pub piano_axis: bool,
+    pub axis: bool,
+    pub chroma: ChromaView,
+    pub on_end: OnEnd,
+    pub progress_bar: ProgressBar,
+    pub progress_bar_thickness: f32,
+    pub progress_bar_color: Color,
+    pub time_label: bool,
+    pub analyzer: String,
+    pub bins_per_octave: u32,
+    pub title_bar: bool,
+    // Window settings, only applied when the window opens
+    pub fullscreen: Fullscreen,
+    pub position: Placement,
+    pub monitor: usize,
+    pub always_on_top: bool,
+    pub transparent: bool,
+    // Transparent, click-through window along the bottom of the screen
+    pub overlay: bool,
+    pub debug: bool,
+    pub volume: u32,
+    pub speed: f32,
+    pub keybindings: Keybindings,
+}
+
+#[derive(Resource)]
+pub struct AppState {
+    pub sink: rodio::Sink,
+    // Used to start a new sink when seeking
+    pub stream_handle: OutputStreamHandle,
+    pub display_str: String,
+    pub display_start_time: f64,
+    pub profiles: Vec<String>,
+    pub show_help: bool,
+    pub paused: bool,
+    // A-B loop points in seconds. With only a start, playback loops from
+    // there at the end of the track.
+    pub loop_start: Option<f64>,
+    pub loop_end: Option<f64>,
+    // Index of the playing track in args.playlist
+    pub track_index: usize,
+    pub fft_fps: u32,
+    pub rendering_fps: u32,
+}
+
+#[derive(Resource)]
+pub struct FFTState {
+    pub fft: Vec<Vec<f32>>,
+    pub bar_freqs: Vec<f32>,
+    // Level in dB of each bar, for the axis and inspector
+    pub levels: BarLevels,
+    pub curr_bars: BarHandles,
+    pub despawn_handles: Vec<Entity>,
+    pub total_frame_counter: usize,
+    pub fft_frame_counter: usize,
+    pub fft_timer: stopwatch::Stopwatch,
+    // Playback speed the clock runs at, and the playback position minus
+    // fft_timer's elapsed time scaled by it. Both change when seeking or
+    // changing speed.
+    pub speed: f64,
+    pub seek_offset: f64,
+    // Scale of the bar heights, below 1 while fading out at the end of a track
+    pub fade: f32,
+}
+
+pub fn analyzer_params(audio_buffer: &AudioBuffer, args: &FFTArgs) -> AnalyzerParams {
+    AnalyzerParams {
+        sample_rate: audio_buffer.sample_rate,
+        n_channels: audio_buffer.n_channels,
+        freq_resolution: args.freq_resolution,
+        min_freq: args.min_freq,
+        max_freq: args.max_freq,
+        bins_per_octave: args.bins_per_octave,
+    }
+}
+
+// Mirrors a frame of band values around the centre and interpolates between
+// them, giving one value per bar
+fn layout_bars(frame: &mut Vec<f32>, smoothness: u32) {
+    let mut reversed = frame.clone();
+    reversed.reverse();
+    reversed.append(frame);
+    *frame = reversed;
+    space_interpolate(frame, smoothness);
+}
+
+// Bar values per frame, the frequency of each bar, their levels and the
+// chromagram
+pub type Spectrum = (Vec<Vec<f32>>, Vec<f32>, BarLevels, Chroma);
+
+pub fn compute_and_preprocess_fft(audio_buffer: &AudioBuffer, args: &FFTArgs) -> Result<Spectrum> {
+    let now = Instant::now();
+    let params = analyzer_params(audio_buffer, args);
+    let mut analyzer = build_analyzer(&args.analyzer, &params)?;
+    let mut fft = run_analyzer(audio_buffer, analyzer.as_mut(), FFT_FPS)?;
+    // Smoothing drops AVERAGING_WINDOW frames at each end, and playback
+    // interpolates between consecutive frames
+    let min_frames = 2 * AVERAGING_WINDOW as usize + 2;
+    if fft.num_frames < min_frames {
+        return Err(FftvizError::InvalidValue(format!(
+            "The track or selected range is too short to visualize, it must be at least {:.2} seconds long.",
+            (min_frames - 1) as f64 * TIME_BETWEEN_FFT_FRAMES
+        )));
+    }
+    if fft.num_bars < MIN_BANDS {
+        return Err(FftvizError::Analysis(format!(
+            "only {} frequency bands between min_freq and max_freq, at least {} are needed. Widen the range.",
+            fft.num_bars,
+            MIN_BANDS
+        )));
+    }
+
+    if args.debug {
+        println!("Computed FFT in {:?}", now.elapsed());
+    }
+
+    let bar_freqs = bar_frequencies(&fft.freqs, args.smoothness);
+
+    let now = Instant::now();
+    fft = smooth_fft(fft, AVERAGING_WINDOW);
+    // From the smoothed frames, so chroma frames line up with the bars
+    let chroma = compute_chroma(&fft);
+    let scale = LevelScale::new(&fft);
+    let mut levels = fft
+        .fft
+        .iter()
+        .map(|frame| frame.iter().map(|v| scale.db(*v)).collect())
+        .collect::<Vec<Vec<f32>>>();
+    fft = intensity_normalize_fft(fft, RESCALING_THRESHOLDS, INTENSITY_RESCALING);
+    fft = frequency_normalize_fft(fft, FREQ_RESCALING);
+    if args.debug {
+        println!("Normalized in {:?}", now.elapsed());
+    }
+
+    let now = Instant::now();
+    let mut fft_vec = fft.fft;
+    for frame in fft_vec.iter_mut().chain(levels.iter_mut()) {
+        layout_bars(frame, args.smoothness);
+    }
+    if args.debug {
+        println!("Interpolated in {:?}", now.elapsed());
+    }
+
+    let levels = BarLevels { db: levels, scale };
+    Ok((fft_vec, bar_freqs, levels, chroma))
+}
+
+// Decoded track (its --start/--end range) and analysis results
+pub struct LoadedTrack {
+    pub audio_buffer: AudioBuffer,
+    pub fft_vec: Vec<Vec<f32>>,
+    pub bar_freqs: Vec<f32>,
+    pub levels: BarLevels,
+    pub chroma: Chroma,
+    pub loudness: Loudness,
+    pub info: TrackInfo,
+}
+
+pub fn load_track(path: &PathBuf, args: &FFTArgs) -> Result<LoadedTrack> {
+    // Compute and preprocess FFT (spatial + temporal interpolation and normalization)
+    let audio_buffer = decode_audio(path)?;
+    let duration = track_duration(&audio_buffer);
+    if args.start >= duration {
+        return Err(FftvizError::InvalidValue(format!(
+            "--start {} is past the end of {} ({}).",
+            format_time(args.start),
+            path.display(),
+            format_time(duration)
+        )));
+    }
+    let audio_buffer = audio_buffer.slice(args.start, args.end);
+    let (fft_vec, bar_freqs, levels, chroma) = compute_and_preprocess_fft(&audio_buffer, args)?;
+    let loudness = compute_loudness(&audio_buffer);
+    let info = read_track_info(path);
+
+    Ok(LoadedTrack {
+        audio_buffer,
+        fft_vec,
+        bar_freqs,
+        levels,
+        chroma,
+        loudness,
+        info,
+    })
+}
+
+// First track and a sink already playing it
+struct PreparedTrack {
+    track: LoadedTrack,
+    sink: rodio::Sink,
+    stream_handle: OutputStreamHandle,
+}
+
+// Registers the visualizer's resources and systems, and plays `args.file_path`
+// on the given output stream. The window is left to the host app, and the
+// output stream must be kept alive for as long as the app runs.
+pub struct FftVizPlugin {
+    pub args: FFTArgs,
+    // Command line and env settings that config reloads are layered under
+    pub layers: ArgLayers,
+    pub spawn_camera: bool,
+    // Whether the plugin sets ClearColor to the background color. Off by
+    // default, so the host keeps control of it.
+    pub set_clear_color: bool,
+    prepared: Mutex<Option<PreparedTrack>>,
+}
+
+impl FftVizPlugin {
+    // Decodes and analyzes the track up front, so that failures are returned
+    // here instead of panicking inside `App::add_plugins`
+    pub fn new(args: FFTArgs, stream_handle: &OutputStreamHandle) -> Result<Self> {
+        let track = load_track(&args.file_path, &args)?;
+
+        let sink = rodio::Sink::try_new(stream_handle)?;
+        sink.set_volume(args.volume as f32 / 100.0);
+        sink.set_speed(args.speed);
+        sink.append(track.audio_buffer.source_from(0.0));
+        // Playback starts in build(), along with the timer the bars follow
+        sink.pause();
+
+        Ok(FftVizPlugin {
+            layers: ArgLayers::from_args(&args),
+            args,
+            spawn_camera: true,
+            set_clear_color: false,
+            prepared: Mutex::new(Some(PreparedTrack {
+                track,
+                sink,
+                stream_handle: stream_handle.clone(),
+            })),
+        })
+    }
+}
+
+impl FFTState {
+    // Playback position in seconds
+    pub fn elapsed(&self) -> f64 {
+        self.fft_timer.elapsed().as_secs_f64() * self.speed + self.seek_offset
+    }
+
+    // Position in seconds of the last frame that can be interpolated from
+    pub fn end_time(&self) -> f64 {
+        self.fft.len().saturating_sub(2) as f64 * TIME_BETWEEN_FFT_FRAMES
+    }
+}
+
+fn spawn_camera(mut commands: Commands) {
+    commands.spawn(Camera2dBundle::default());
+}
+
+impl Plugin for FftVizPlugin {
+    fn build(&self, app: &mut App) {
+        // Bevy builds a plugin once, unless it opts out of being unique
+        let Some(prepared) = self.prepared.lock().ok().and_then(|mut p| p.take()) else {
+            return;
+        };
+        let args = self.args.clone();
+        let PreparedTrack {
+            track:
+                LoadedTrack {
+                    audio_buffer,
+                    fft_vec,
+                    bar_freqs,
+                    levels,
+                    chroma,
+                    loudness,
+                    info,
+                },
+            sink,
+            stream_handle,
+        } = prepared;
+
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin);
+        }
+        if self.spawn_camera {
+            app.add_systems(Startup, spawn_camera);
+        }
+        if self.set_clear_color {
+            app.insert_resource(ClearColor(background_color(&args)))
+                .add_systems(Update, update_clear_color);
+        }
+
+        // Reload config.yaml when it changes. Without a config directory there
+        // is nothing to watch.
+        if let Some(cfg_path) = &args.config_path {
+            match ConfigWatcher::new(cfg_path) {
+                Ok(watcher) => {
+                    app.insert_non_send_resource(watcher);
+                }
+                Err(e) => {
+                    if args.debug {
+                        println!("Not watching config for changes: {}", e);
+                    }
+                }
+            }
+        }
+
+        let profiles = config_profile_names(args.config_path.as_deref());
+        let speed = args.speed as f64;
+
+        // Start playback and the timer that keeps fft in sync
+        sink.play();
+        let fft_timer = stopwatch::Stopwatch::start_new();
+
+        app
+            // Insert resources
+            .insert_resource(args)
+            .insert_resource(self.layers.clone())
+            .insert_resource(audio_buffer)
+            .insert_resource(loudness)
+            .insert_resource(chroma)
+            .insert_resource(info)
+            .insert_resource(AppState {
+                sink,
+                stream_handle,
+                display_str: String::new(),
+                display_start_time: 0.0,
+                profiles,
+                show_help: false,
+                paused: false,
+                loop_start: None,
+                loop_end: None,
+                track_index: 0,
+                fft_fps: FFT_FPS,
+                rendering_fps: RENDERING_FPS,
+            })
+            .insert_resource(FFTRecompute::default())
+            .insert_resource(SeekBar::default())
+            .insert_resource(TrackEnd::default())
+            .insert_resource(FFTState {
+                fft: fft_vec,
+                bar_freqs,
+                levels,
+                curr_bars: Vec::new(),
+                despawn_handles: Vec::new(),
+                fft_frame_counter: 0,
+                total_frame_counter: 0,
+                fft_timer,
+                speed,
+                seek_offset: 0.0,
+                fade: 1.0,
+            })
+            // Insert systems
+            .add_systems(Startup, startup)
+            .add_systems(Startup, spawn_level_meters)
+            .add_systems(Startup, spawn_tuner)
+            .add_systems(Startup, spawn_chroma)
+            .add_systems(Startup, spawn_seek_bar)
+            .add_systems(Startup, spawn_time_label)
+            .add_systems(Update, update_ab_loop.before(update_frame_counters))
+            .add_systems(Update, update_track_end.before(update_frame_counters))
+            .add_systems(Update, update_frame_counters)
+            .add_systems(Update, update_fft)
+            .add_systems(Update, ui_example_system)
+            .add_systems(Update, get_keyboard_input)
+            .add_systems(Update, help_overlay)
+            .add_systems(Update, get_mouse_input)
+            .add_systems(Update, update_seek_bar)
+            .add_systems(Update, update_time_label)
+            .add_systems(Update, update_track_label)
+            .add_systems(Update, update_album_art)
+            .add_systems(Update, update_view_settings)
+            .add_systems(Update, update_vectorscope)
+            .add_systems(Update, update_level_meters)
+            .add_systems(Update, update_tuner)
+            .add_systems(Update, (spawn_piano_axis, update_piano_axis).chain())
+            .add_systems(Update, (spawn_axis, update_axis).chain())
+            .add_systems(Update, bar_inspector)
+            .add_systems(Update, update_chroma)
+            .add_systems(Update, watch_config)
+            .add_systems(Update, apply_recompute)
+            .add_systems(Update, apply_next_track);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::sync::Arc;
+
+    fn test_args() -> FFTArgs {
+        let cli_args = CLIArgs::parse_from(["fftviz", "Cargo.toml"]);
+        cli_args_to_fft_args(cli_args, true).unwrap()
+    }
+
+    fn silence(seconds: f64) -> AudioBuffer {
+        AudioBuffer {
+            samples: Arc::new(vec![0; (seconds * 44100.0) as usize * 2]),
+            n_channels: 2,
+            sample_rate: 44100,
+        }
+    }
+
+    #[test]
+    fn rejects_too_short_audio() {
+        let args = test_args();
+        for seconds in [0.0, 0.1, 0.2] {
+            assert!(matches!(
+                compute_and_preprocess_fft(&silence(seconds), &args),
+                Err(FftvizError::InvalidValue(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_too_narrow_cqt_range() {
+        let mut args = test_args();
+        args.analyzer = String::from("cqt");
+        args.min_freq = 1000.0;
+        args.max_freq = 1100.0;
+        assert!(matches!(
+            compute_and_preprocess_fft(&silence(1.0), &args),
+            Err(FftvizError::Analysis(_))
+        ));
+    }
+
+    // The gui's frequency sliders stop at the narrowest range each analyzer
+    // can still split into enough bands
+    #[test]
+    fn narrowest_slider_range_is_analyzable() {
+        let audio = silence(1.0);
+        for name in analyzer_names() {
+            let mut args = test_args();
+            args.analyzer = String::from(name);
+            let spacing = band_spacing(name, &analyzer_params(&audio, &args)).unwrap();
+
+            (args.min_freq, args.max_freq) = (1000.0, spacing.max_freq_above(1000.0, MIN_BANDS));
+            assert!(compute_and_preprocess_fft(&audio, &args).is_ok(), "{}", name);
+            (args.min_freq, args.max_freq) = (spacing.min_freq_below(1000.0, MIN_BANDS), 1000.0);
+            assert!(compute_and_preprocess_fft(&audio, &args).is_ok(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn analyzes_short_audio() {
+        let args = test_args();
+        let (fft_vec, bar_freqs, levels, chroma) =
+            compute_and_preprocess_fft(&silence(0.5), &args).unwrap();
+        assert!(fft_vec.len() >= 2);
+        assert!(fft_vec.iter().all(|frame| frame.len() == bar_freqs.len()));
+        assert_eq!(levels.db.len(), fft_vec.len());
+        assert!(levels.db.iter().all(|frame| frame.len() == bar_freqs.len()));
+        assert_eq!(chroma.frames.len(), fft_vec.len());
+    }
+
+    // The gui swaps a recomputed spectrum in mid-playback, so the frame
+    // counter has to stay valid whatever the analysis settings
+    #[test]
+    fn recompute_keeps_frame_count() {
+        let audio = silence(1.0);
+        let args = test_args();
+        let (fft_vec, _, _, _) = compute_and_preprocess_fft(&audio, &args).unwrap();
+
+        let changes: [fn(&mut FFTArgs); 4] = [
+            |a| a.smoothness = 3,
+            |a| a.freq_resolution = 40,
+            |a| a.min_freq = 200.0,
+            |a| a.max_freq = 2000.0,
+        ];
+        for change in changes {
+            let mut changed = args.clone();
+            change(&mut changed);
+            let (new_vec, bar_freqs, _, _) =
+                compute_and_preprocess_fft(&audio, &changed).unwrap();
+            assert_eq!(new_vec.len(), fft_vec.len());
+            assert!(new_vec.iter().all(|frame| frame.len() == bar_freqs.len()));
+        }
+    }
+
+    // A gridline sits where normalization puts a band steady at its level
+    #[test]
+    fn gridlines_match_normalized_bars() {
+        let magnitudes = [0.03, 0.12, 0.33, 0.42, 0.7];
+        let fft = FFT {
+            fft: vec![magnitudes.to_vec(); 10],
+            freqs: vec![100.0; magnitudes.len()],
+            num_frames: 10,
+            num_bars: magnitudes.len(),
+            min: 0.0,
+            max: 1.0,
+        };
+        let fft = smooth_fft(fft, AVERAGING_WINDOW);
+        let scale = LevelScale::new(&fft);
+        let db = fft.fft[0].iter().map(|v| scale.db(*v)).collect::<Vec<f32>>();
+        let fft = intensity_normalize_fft(fft, RESCALING_THRESHOLDS, INTENSITY_RESCALING);
+
+        for (i, m) in magnitudes.iter().enumerate() {
+            assert!((db[i] - 20.0 * m.log10()).abs() < 1e-3);
+            let v = scale.bar_value(db[i], RESCALING_THRESHOLDS, INTENSITY_RESCALING);
+            assert!((v - fft.fft[0][i]).abs() < 1e-4);
+        }
+    }
+}
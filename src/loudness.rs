@@ -0,0 +1,306 @@
+use crate::audio::AudioBuffer;
+use bevy::prelude::Resource;
+use std::f64::consts::PI;
+
+// EBU R128 block and window sizes
+pub const BLOCK_DURATION: f64 = 0.1;
+const MOMENTARY_BLOCKS: usize = 4;
+const SHORT_TERM_BLOCKS: usize = 30;
+
+// Gating thresholds for integrated loudness (BS.1770-4)
+const ABSOLUTE_GATE: f64 = -70.0;
+const RELATIVE_GATE: f64 = -10.0;
+const HISTOGRAM_STEP: f64 = 0.1;
+const HISTOGRAM_BINS: usize = 750;
+
+// Lowest value reported for silent blocks, in dB
+pub const LOUDNESS_FLOOR: f32 = -70.0;
+
+// True peak oversampling
+const OVERSAMPLING: usize = 4;
+const TAPS_PER_PHASE: usize = 12;
+
+// Per-block levels for the whole track, in dBFS (peak, rms) and LUFS (the rest)
+#[derive(Resource, Debug)]
+pub struct Loudness {
+    pub peak: Vec<f32>,
+    pub rms: Vec<f32>,
+    pub momentary: Vec<f32>,
+    pub short_term: Vec<f32>,
+    pub integrated: Vec<f32>,
+}
+
+impl Loudness {
+    pub fn num_blocks(&self) -> usize {
+        self.peak.len()
+    }
+
+    // Highest sample peak over the `hold_blocks` blocks ending at `i`
+    pub fn peak_hold(&self, i: usize, hold_blocks: usize) -> f32 {
+        self.peak[i.saturating_sub(hold_blocks)..=i]
+            .iter()
+            .fold(LOUDNESS_FLOOR, |a, b| a.max(*b))
+    }
+
+    pub fn integrated_lufs(&self) -> f32 {
+        *self.integrated.last().unwrap_or(&LOUDNESS_FLOOR)
+    }
+}
+
+// Transposed direct form II biquad
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 3],
+    z: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.z[0];
+        self.z[0] = self.b[1] * x - self.a[1] * y + self.z[1];
+        self.z[1] = self.b[2] * x - self.a[2] * y;
+        y
+    }
+}
+
+// K-weighting (high shelf + RLB high pass), with coefficients derived for any
+// sample rate the same way libebur128 does
+fn k_weighting(sample_rate: u32) -> [Biquad; 2] {
+    let fs = sample_rate as f64;
+
+    let (f0, gain, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+    let k = (PI * f0 / fs).tan();
+    let vh = 10f64.powf(gain / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad {
+        b: [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        a: [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        z: [0.0; 2],
+    };
+
+    let (f0, q) = (38.13547087602444, 0.5003270373238773);
+    let k = (PI * f0 / fs).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad {
+        b: [1.0, -2.0, 1.0],
+        a: [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        z: [0.0; 2],
+    };
+
+    [shelf, high_pass]
+}
+
+// Surround channels are weighted up and the LFE channel is ignored (5.1 layout)
+fn channel_weights(n_channels: usize) -> Vec<f64> {
+    match n_channels {
+        6 => vec![1.0, 1.0, 1.0, 0.0, 1.41, 1.41],
+        n => vec![1.0; n],
+    }
+}
+
+fn to_db(amplitude: f64) -> f32 {
+    (20.0 * amplitude.log10()).max(LOUDNESS_FLOOR as f64) as f32
+}
+
+fn to_lufs(energy: f64) -> f64 {
+    -0.691 + 10.0 * energy.log10()
+}
+
+fn mean_energy(energies: &[f64]) -> f64 {
+    energies.iter().sum::<f64>() / energies.len() as f64
+}
+
+pub fn compute_loudness(audio: &AudioBuffer) -> Loudness {
+    let n_channels = audio.n_channels;
+    let block_len = (audio.sample_rate as f64 * BLOCK_DURATION) as usize;
+    let weights = channel_weights(n_channels);
+    let mut filters = (0..n_channels)
+        .map(|_| k_weighting(audio.sample_rate))
+        .collect::<Vec<[Biquad; 2]>>();
+
+    let (mut peak, mut rms, mut block_energy) = (Vec::new(), Vec::new(), Vec::new());
+    for block in audio.samples.chunks(block_len * n_channels) {
+        let n_frames = (block.len() / n_channels) as f64;
+        let mut channel_energy = vec![0.0; n_channels];
+        let (mut block_peak, mut sum_squares) = (0.0f64, 0.0);
+
+        for frame in block.chunks(n_channels) {
+            for (c, s) in frame.iter().enumerate() {
+                let x = *s as f64 / 32768.0;
+                block_peak = block_peak.max(x.abs());
+                sum_squares += x * x;
+
+                let [shelf, high_pass] = &mut filters[c];
+                let y = high_pass.process(shelf.process(x));
+                channel_energy[c] += y * y;
+            }
+        }
+
+        peak.push(to_db(block_peak));
+        rms.push(to_db((sum_squares / (n_frames * n_channels as f64)).sqrt()));
+        block_energy.push(
+            channel_energy
+                .iter()
+                .zip(weights.iter())
+                .map(|(e, w)| e * w / n_frames)
+                .sum::<f64>(),
+        );
+    }
+
+    // Gated integrated loudness, tracked as a running value with a histogram of
+    // momentary (400ms, 75% overlap) block loudnesses
+    let mut hist_count = vec![0usize; HISTOGRAM_BINS];
+    let mut hist_energy = vec![0.0f64; HISTOGRAM_BINS];
+    let (mut momentary, mut short_term, mut integrated) = (Vec::new(), Vec::new(), Vec::new());
+
+    for i in 0..block_energy.len() {
+        let m = mean_energy(&block_energy[(i + 1).saturating_sub(MOMENTARY_BLOCKS)..=i]);
+        let s = mean_energy(&block_energy[(i + 1).saturating_sub(SHORT_TERM_BLOCKS)..=i]);
+        momentary.push((to_lufs(m) as f32).max(LOUDNESS_FLOOR));
+        short_term.push((to_lufs(s) as f32).max(LOUDNESS_FLOOR));
+
+        let m_lufs = to_lufs(m);
+        if i + 1 >= MOMENTARY_BLOCKS && m_lufs >= ABSOLUTE_GATE {
+            let bin = (((m_lufs - ABSOLUTE_GATE) / HISTOGRAM_STEP) as usize).min(HISTOGRAM_BINS - 1);
+            hist_count[bin] += 1;
+            hist_energy[bin] += m;
+        }
+        integrated.push(gated_loudness(&hist_count, &hist_energy));
+    }
+
+    Loudness {
+        peak,
+        rms,
+        momentary,
+        short_term,
+        integrated,
+    }
+}
+
+fn gated_loudness(hist_count: &[usize], hist_energy: &[f64]) -> f32 {
+    let count = hist_count.iter().sum::<usize>();
+    if count == 0 {
+        return LOUDNESS_FLOOR;
+    }
+
+    let threshold = to_lufs(hist_energy.iter().sum::<f64>() / count as f64) + RELATIVE_GATE;
+    let first_bin = (((threshold - ABSOLUTE_GATE) / HISTOGRAM_STEP).max(0.0) as usize)
+        .min(HISTOGRAM_BINS - 1);
+
+    let count = hist_count[first_bin..].iter().sum::<usize>();
+    let energy = hist_energy[first_bin..].iter().sum::<f64>();
+    match count {
+        0 => LOUDNESS_FLOOR,
+        n => (to_lufs(energy / n as f64) as f32).max(LOUDNESS_FLOOR),
+    }
+}
+
+// Windowed sinc interpolation filter, split into `OVERSAMPLING` phases
+fn oversampling_filter() -> Vec<f64> {
+    let n_taps = OVERSAMPLING * TAPS_PER_PHASE;
+    let center = (n_taps - 1) as f64 / 2.0;
+    (0..n_taps)
+        .map(|i| {
+            let x = (i as f64 - center) / OVERSAMPLING as f64;
+            let sinc = match x {
+                0.0 => 1.0,
+                x => (PI * x).sin() / (PI * x),
+            };
+            let window = 0.5 - 0.5 * (2.0 * PI * i as f64 / (n_taps - 1) as f64).cos();
+            sinc * window
+        })
+        .collect()
+}
+
+// Maximum inter-sample peak of the whole track in dBTP
+pub fn compute_true_peak(audio: &AudioBuffer) -> f32 {
+    let n_channels = audio.n_channels;
+    let filter = oversampling_filter();
+    let mut history = vec![[0.0f64; TAPS_PER_PHASE]; n_channels];
+    let mut true_peak = 0.0f64;
+
+    for frame in audio.samples.chunks(n_channels) {
+        for (c, s) in frame.iter().enumerate() {
+            let x = *s as f64 / 32768.0;
+            let h = &mut history[c];
+            h.rotate_right(1);
+            h[0] = x;
+
+            true_peak = true_peak.max(x.abs());
+            for phase in 0..OVERSAMPLING {
+                let y = h
+                    .iter()
+                    .enumerate()
+                    .map(|(k, v)| filter[phase + OVERSAMPLING * k] * v)
+                    .sum::<f64>();
+                true_peak = true_peak.max(y.abs());
+            }
+        }
+    }
+    to_db(true_peak)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    // `seconds` of a sine at `freq` Hz and `amplitude` (peak, in [0, 1]) on
+    // every channel
+    fn sine(freq: f64, amplitude: f64, phase: f64, seconds: f64, n_channels: usize) -> AudioBuffer {
+        let sample_rate = 48000;
+        let samples = (0..(seconds * sample_rate as f64) as usize)
+            .flat_map(|i| {
+                let t = i as f64 / sample_rate as f64;
+                let x = amplitude * (2.0 * PI * freq * t + phase).sin();
+                vec![(x * 32768.0).round() as i16; n_channels]
+            })
+            .collect();
+        AudioBuffer {
+            samples: Arc::new(samples),
+            n_channels,
+            sample_rate,
+        }
+    }
+
+    #[test]
+    fn stereo_sine_at_1khz_reads_its_level_in_lufs() {
+        // EBU Tech 3341: a stereo 1 kHz sine at -20 dBFS measures -20 LUFS
+        let loudness = compute_loudness(&sine(1000.0, 0.1, 0.0, 5.0, 2));
+        assert!((loudness.integrated_lufs() + 20.0).abs() < 0.1);
+        assert!((loudness.momentary.last().unwrap() + 20.0).abs() < 0.1);
+        assert!((loudness.short_term.last().unwrap() + 20.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn sine_peak_and_rms() {
+        let loudness = compute_loudness(&sine(1000.0, 0.5, 0.0, 1.0, 1));
+        let (peak, rms) = (loudness.peak[5], loudness.rms[5]);
+        assert!((peak - 20.0 * 0.5f32.log10()).abs() < 0.05);
+        assert!((peak - rms - 3.01).abs() < 0.05);
+    }
+
+    #[test]
+    fn silence_is_at_the_floor() {
+        let loudness = compute_loudness(&sine(1000.0, 0.0, 0.0, 1.0, 2));
+        assert_eq!(loudness.integrated_lufs(), LOUDNESS_FLOOR);
+        assert!(loudness.peak.iter().all(|p| *p == LOUDNESS_FLOOR));
+        assert_eq!(compute_true_peak(&sine(1000.0, 0.0, 0.0, 1.0, 2)), LOUDNESS_FLOOR);
+    }
+
+    #[test]
+    fn true_peak_finds_peaks_between_samples() {
+        // A quarter of the sample rate, sampled 45 degrees off its peaks: every
+        // sample is 3 dB below the peak of the continuous signal
+        let audio = sine(12000.0, 0.5, PI / 4.0, 1.0, 1);
+        let sample_peak = compute_loudness(&audio).peak[5];
+        let true_peak = compute_true_peak(&audio);
+        assert!((sample_peak - 20.0 * (0.5f32 * 0.5f32.sqrt()).log10()).abs() < 0.05);
+        assert!((true_peak - 20.0 * 0.5f32.log10()).abs() < 0.5);
+    }
+}
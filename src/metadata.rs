@@ -0,0 +1,214 @@
+use crate::format_time;
+use bevy::prelude::*;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::render::texture::{CompressedImageFormats, ImageSampler, ImageType};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::path::Path;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::{MetadataOptions, MetadataRevision, StandardTagKey, StandardVisualKey};
+use symphonia::core::probe::Hint;
+
+// Side of the blurred cover in pixels, before it is stretched over the window
+const BLURRED_COVER_SIZE: u32 = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum AlbumArt {
+    Off,
+    // The cover in the top right corner
+    Sprite,
+    // The cover blurred and darkened behind the bars
+    Background,
+}
+
+// Tags and cover art of the playing track
+#[derive(Resource, Clone, Default)]
+pub struct TrackInfo {
+    pub file_name: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub year: Option<u32>,
+    pub cover: Option<Image>,
+}
+
+// Reads the ID3, Vorbis, FLAC or MP4 tags of `path`. Files without (readable)
+// tags only get their file name.
+pub fn read_track_info(path: &Path) -> TrackInfo {
+    let mut info = TrackInfo {
+        file_name: path.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+        ..default()
+    };
+    let Ok(file) = File::open(path) else {
+        return info;
+    };
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(extension);
+    }
+    let Ok(mut probed) = symphonia::default::get_probe().format(
+        &hint,
+        MediaSourceStream::new(Box::new(file), Default::default()),
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    ) else {
+        return info;
+    };
+
+    // Tags of the container itself, then ones in front of it (like ID3v2 in an mp3)
+    if let Some(revision) = probed.format.metadata().skip_to_latest() {
+        info.add_tags(revision);
+    }
+    if let Some(revision) = probed.metadata.get().as_mut().and_then(|m| m.skip_to_latest()) {
+        info.add_tags(revision);
+    }
+    info
+}
+
+impl TrackInfo {
+    // Fills in the tags still missing from `revision`
+    fn add_tags(&mut self, revision: &MetadataRevision) {
+        for tag in revision.tags() {
+            // RIFF INFO values keep their NUL terminator
+            let value = tag.value.to_string().trim_end_matches('\0').trim().to_string();
+            if value.is_empty() {
+                continue;
+            }
+            let field = match tag.std_key {
+                Some(StandardTagKey::TrackTitle) => &mut self.title,
+                Some(StandardTagKey::Artist) => &mut self.artist,
+                Some(StandardTagKey::Album) => &mut self.album,
+                Some(StandardTagKey::Date) => {
+                    if self.year.is_none() {
+                        self.year = value.get(..4).and_then(|y| y.parse().ok());
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+            field.get_or_insert(value);
+        }
+
+        if self.cover.is_some() {
+            return;
+        }
+        // The front cover, or any picture if there is none
+        let visuals = revision.visuals();
+        let visual = visuals
+            .iter()
+            .find(|v| v.usage == Some(StandardVisualKey::FrontCover))
+            .or_else(|| visuals.first());
+        self.cover = visual.and_then(|v| {
+            Image::from_buffer(
+                &v.data,
+                ImageType::MimeType(&v.media_type),
+                CompressedImageFormats::NONE,
+                true,
+                ImageSampler::Default,
+                RenderAssetUsages::RENDER_WORLD,
+            )
+            .ok()
+        });
+    }
+
+    // Fills in {title}, {artist}, {album}, {year}, {file}, {elapsed},
+    // {duration} and {remaining}. A missing title falls back to the file name,
+    // other missing tags are left empty.
+    pub fn format(&self, template: &str, elapsed: f64, duration: f64) -> String {
+        let title = self.title.as_deref().unwrap_or(&self.file_name);
+        let year = self.year.map(|y| y.to_string()).unwrap_or_default();
+        [
+            ("{title}", title),
+            ("{artist}", self.artist.as_deref().unwrap_or_default()),
+            ("{album}", self.album.as_deref().unwrap_or_default()),
+            ("{year}", year.as_str()),
+            ("{file}", self.file_name.as_str()),
+            ("{elapsed}", format_time(elapsed).as_str()),
+            ("{duration}", format_time(duration).as_str()),
+            ("{remaining}", format_time(duration - elapsed).as_str()),
+        ]
+        .iter()
+        .fold(template.to_string(), |s, (key, value)| s.replace(key, value))
+    }
+}
+
+// The cover shrunk to a few pixels, which linear filtering turns into a blur
+// when it is stretched over the window. None for formats other than 8-bit RGBA.
+pub fn blurred_cover(cover: &Image) -> Option<Image> {
+    if !matches!(
+        cover.texture_descriptor.format,
+        TextureFormat::Rgba8UnormSrgb | TextureFormat::Rgba8Unorm
+    ) {
+        return None;
+    }
+    let (w, h) = (cover.width() as usize, cover.height() as usize);
+    let n = BLURRED_COVER_SIZE as usize;
+    if w < n || h < n {
+        return None;
+    }
+
+    // Averages each block of pixels into one
+    let mut data = Vec::with_capacity(n * n * 4);
+    for by in 0..n {
+        for bx in 0..n {
+            let (x0, x1) = (bx * w / n, (bx + 1) * w / n);
+            let (y0, y1) = (by * h / n, (by + 1) * h / n);
+            let mut sum = [0u64; 4];
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    let i = (y * w + x) * 4;
+                    for (s, v) in sum.iter_mut().zip(&cover.data[i..i + 4]) {
+                        *s += *v as u64;
+                    }
+                }
+            }
+            let count = ((x1 - x0) * (y1 - y0)) as u64;
+            data.extend(sum.iter().map(|s| (s / count) as u8));
+        }
+    }
+    Some(Image::new(
+        Extent3d {
+            width: BLURRED_COVER_SIZE,
+            height: BLURRED_COVER_SIZE,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        cover.texture_descriptor.format,
+        RenderAssetUsages::RENDER_WORLD,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_templates() {
+        let info = TrackInfo {
+            file_name: String::from("song.flac"),
+            title: Some(String::from("Song")),
+            artist: Some(String::from("Band")),
+            year: Some(1999),
+            ..default()
+        };
+        assert_eq!(
+            info.format("{artist} - {title} ({year}) {elapsed}/{duration}", 65.0, 3725.0),
+            "Band - Song (1999) 1:05/1:02:05"
+        );
+        assert_eq!(info.format("{album}|{file}|{remaining}", 60.0, 90.0), "|song.flac|0:30");
+    }
+
+    #[test]
+    fn title_falls_back_to_the_file_name() {
+        let info = TrackInfo {
+            file_name: String::from("song.flac"),
+            ..default()
+        };
+        assert_eq!(info.format("{title}", 0.0, 0.0), "song.flac");
+    }
+}
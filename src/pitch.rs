@@ -0,0 +1,136 @@
+// Names of the 12 pitch classes, from C
+pub const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+// YIN parameters
+const YIN_THRESHOLD: f32 = 0.15;
+const SILENCE_RMS: f32 = 0.01;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Note {
+    pub name: &'static str,
+    pub octave: i32,
+    pub cents: f32,
+}
+
+// Fractional MIDI note number of a frequency (A4 = 440Hz = 69)
+pub fn frequency_to_midi(freq: f32) -> f32 {
+    69.0 + 12.0 * (freq / 440.0).log2()
+}
+
+pub fn is_sharp(midi: i32) -> bool {
+    NOTE_NAMES[midi.rem_euclid(12) as usize].ends_with('#')
+}
+
+pub fn nearest_note(freq: f32) -> Note {
+    let midi = frequency_to_midi(freq);
+    let nearest = midi.round() as i32;
+    Note {
+        name: NOTE_NAMES[nearest.rem_euclid(12) as usize],
+        octave: nearest.div_euclid(12) - 1,
+        cents: (midi - nearest as f32) * 100.0,
+    }
+}
+
+// Estimates the fundamental frequency of a mono frame with the YIN algorithm.
+// The frame must hold at least two periods of `min_freq`.
+pub fn detect_pitch(samples: &[f32], sample_rate: u32, min_freq: f32, max_freq: f32) -> Option<f32> {
+    let max_tau = (sample_rate as f32 / min_freq) as usize;
+    let min_tau = ((sample_rate as f32 / max_freq) as usize).max(2);
+    if samples.len() < 2 * max_tau {
+        return None;
+    }
+
+    let rms = (samples.iter().map(|x| x * x).sum::<f32>() / samples.len() as f32).sqrt();
+    if rms < SILENCE_RMS {
+        return None;
+    }
+
+    // Difference function over a window that leaves room for the largest lag
+    let w = samples.len() - max_tau;
+    let mut cmnd = vec![1.0; max_tau + 1];
+    let mut running_sum = 0.0;
+    for tau in 1..=max_tau {
+        let d = (0..w)
+            .map(|j| {
+                let diff = samples[j] - samples[j + tau];
+                diff * diff
+            })
+            .sum::<f32>();
+        running_sum += d;
+        if running_sum > 0.0 {
+            cmnd[tau] = d * tau as f32 / running_sum;
+        }
+    }
+
+    // First dip under the threshold, followed down to its local minimum
+    let mut tau = min_tau;
+    while tau < max_tau && cmnd[tau] >= YIN_THRESHOLD {
+        tau += 1;
+    }
+    while tau + 1 < max_tau && cmnd[tau + 1] < cmnd[tau] {
+        tau += 1;
+    }
+    if tau >= max_tau {
+        return None;
+    }
+
+    // Parabolic interpolation around the minimum for sub-sample accuracy
+    let (a, b, c) = (cmnd[tau - 1], cmnd[tau], cmnd[tau + 1]);
+    let denom = a - 2.0 * b + c;
+    let shift = match denom.abs() > f32::EPSILON {
+        true => 0.5 * (a - c) / denom,
+        false => 0.0,
+    };
+    Some(sample_rate as f32 / (tau as f32 + shift))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    fn sine(freq: f32, sample_rate: u32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| 0.5 * (2.0 * PI * freq * i as f32 / sample_rate as f32).sin())
+            .collect()
+    }
+
+    #[test]
+    fn nearest_note_of_a4_and_neighbours() {
+        let a4 = nearest_note(440.0);
+        assert_eq!((a4.name, a4.octave), ("A", 4));
+        assert!(a4.cents.abs() < 1e-3);
+
+        let c4 = nearest_note(261.63);
+        assert_eq!((c4.name, c4.octave), ("C", 4));
+
+        // Just under a quarter tone above A4 is still A4, 49 cents sharp
+        let sharp = nearest_note(440.0 * 2f32.powf(0.49 / 12.0));
+        assert_eq!(sharp.name, "A");
+        assert!((sharp.cents - 49.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn sharps() {
+        assert!(is_sharp(61));
+        assert!(!is_sharp(60));
+        assert!(!is_sharp(-1));
+    }
+
+    #[test]
+    fn yin_finds_the_pitch_of_a_sine() {
+        for freq in [82.41, 220.0, 440.0, 1046.5] {
+            let detected = detect_pitch(&sine(freq, 44100, 4096), 44100, 50.0, 2000.0).unwrap();
+            let cents = 1200.0 * (detected / freq).log2();
+            assert!(cents.abs() < 5.0, "{} Hz detected as {} Hz", freq, detected);
+        }
+    }
+
+    #[test]
+    fn yin_ignores_silence_and_short_frames() {
+        assert_eq!(detect_pitch(&vec![0.0; 4096], 44100, 50.0, 2000.0), None);
+        assert_eq!(detect_pitch(&sine(440.0, 44100, 1000), 44100, 50.0, 2000.0), None);
+    }
+}
@@ -0,0 +1,92 @@
+use crate::error::Result;
+use crate::{AppState, AudioBuffer, FFTArgs, FFTState, TIME_BETWEEN_FFT_FRAMES};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+// What happens once the last frame of a track is reached
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum OnEnd {
+    // Fade out and exit
+    Quit,
+    // Start the track over
+    Loop,
+    // Pause on the last frame, starting over when playback resumes
+    Hold,
+    // Fade out and play the next file given on the command line, exiting
+    // after the last one
+    Next,
+}
+
+// Length of the track (or its --start/--end range) in seconds
+pub fn track_duration(audio: &AudioBuffer) -> f64 {
+    audio.num_frames() as f64 / audio.sample_rate as f64
+}
+
+// Time like 1:05 or 1:02:03
+pub fn format_time(seconds: f64) -> String {
+    let seconds = seconds.max(0.0) as u64;
+    let (h, m, s) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    match h {
+        0 => format!("{}:{:02}", m, s),
+        _ => format!("{}:{:02}:{:02}", h, m, s),
+    }
+}
+
+// Edge of the window the seek bar stays visible along as a progress bar.
+// Off only shows it while the pointer is near the bottom edge.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ProgressBar {
+    Off,
+    Top,
+    Bottom,
+}
+
+pub fn toggle_pause(app_state: &mut AppState, fft_state: &mut FFTState) {
+    app_state.paused = !app_state.paused;
+    if app_state.sink.is_paused() {
+        app_state.sink.play();
+        fft_state.fft_timer.start();
+    } else {
+        app_state.sink.pause();
+        fft_state.fft_timer.stop();
+    }
+}
+
+// Continues playback from `position` seconds, keeping the paused state.
+// rodio's Sink can't seek, so a new sink is started playing the decoded
+// track from there, replacing (and stopping) the current one.
+pub fn seek(
+    position: f64,
+    app_state: &mut AppState,
+    fft_state: &mut FFTState,
+    args: &FFTArgs,
+    audio: &AudioBuffer,
+) -> Result<()> {
+    // Stay clear of the last fft frame, which ends playback
+    let last = (track_duration(audio) - 2.0 * TIME_BETWEEN_FFT_FRAMES).max(0.0);
+    let position = position.clamp(0.0, last);
+
+    let sink = rodio::Sink::try_new(&app_state.stream_handle)?;
+    sink.set_volume(args.volume as f32 / 100.0);
+    sink.set_speed(fft_state.speed as f32);
+    if app_state.paused {
+        sink.pause();
+    }
+    sink.append(audio.source_from(position));
+
+    app_state.sink = sink;
+    fft_state.seek_offset = position - fft_state.fft_timer.elapsed().as_secs_f64() * fft_state.speed;
+    Ok(())
+}
+
+// Changes the playback speed, keeping the current position. rodio speeds the
+// track up by resampling, so pitch follows speed. There is no pitch-preserving
+// time stretch.
+pub fn set_speed(speed: f32, app_state: &mut AppState, fft_state: &mut FFTState) {
+    let position = fft_state.elapsed();
+    fft_state.speed = speed as f64;
+    fft_state.seek_offset = position - fft_state.fft_timer.elapsed().as_secs_f64() * fft_state.speed;
+    app_state.sink.set_speed(speed);
+}
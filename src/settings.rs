@@ -0,0 +1,201 @@
+use crate::config::*;
+use crate::error::{FftvizError, Result};
+use serde_yaml::Value;
+use std::path::{Path, PathBuf};
+
+// Where a setting's value came from, in increasing order of precedence
+#[derive(Debug, Clone, PartialEq)]
+pub enum SettingSource {
+    Default,
+    SystemConfig(PathBuf),
+    UserConfig(PathBuf),
+    Profile(String),
+    Env,
+    Cli,
+}
+
+impl SettingSource {
+    // Where `key` was set, e.g. "--volume" or "FFTVIZ_VOLUME"
+    pub fn origin(&self, key: &str) -> String {
+        match self {
+            SettingSource::Default => String::from("default"),
+            SettingSource::SystemConfig(path) | SettingSource::UserConfig(path) => {
+                path.display().to_string()
+            }
+            SettingSource::Profile(name) => format!("profile {}", name),
+            SettingSource::Env => env_var_name(key),
+            SettingSource::Cli => format!("--{}", cli_flag(key)),
+        }
+    }
+}
+
+pub fn env_var_name(key: &str) -> String {
+    format!("FFTVIZ_{}", key.to_uppercase())
+}
+
+// Profile selected through FFTVIZ_PROFILE, used when --profile is not given
+pub fn env_profile() -> Option<String> {
+    std::env::var(env_var_name("profile")).ok()
+}
+
+// CLI option setting config key `key`
+fn cli_flag(key: &str) -> String {
+    match key {
+        "display_track_name" => String::from("track-name"),
+        "window_width" => String::from("width"),
+        "window_height" => String::from("height"),
+        _ => key.replace('_', "-"),
+    }
+}
+
+// Settings from FFTVIZ_* env vars, e.g. FFTVIZ_VOLUME=80 or FFTVIZ_BAR_COLOR=00FFFF.
+// Values are read as YAML, like they would be in config.yaml.
+pub fn env_layer() -> Result<ConfigFFTArgs> {
+    let mut layer = ConfigFFTArgs::empty();
+    for key in known_keys() {
+        let var = env_var_name(&key);
+        if let Ok(value) = std::env::var(&var) {
+            let single = serde_yaml::from_str::<ConfigFFTArgs>(&format!("{}: {}", key, value))
+                .map_err(|e| FftvizError::InvalidValue(format!("{}: {}", var, e)))?;
+            layer = single.inherit(&layer);
+        }
+    }
+    Ok(layer)
+}
+
+// Fully resolved settings, and the source of each value
+pub struct Settings {
+    pub values: ConfigFFTArgs,
+    pub origins: Vec<(String, SettingSource)>,
+}
+
+impl Settings {
+    pub fn origin(&self, key: &str) -> Option<&SettingSource> {
+        self.origins.iter().find(|(k, _)| k == key).map(|(_, s)| s)
+    }
+
+    // Checks the resolved values, blaming the source of the first bad one
+    pub fn validate(&self) -> Result<()> {
+        match validate_config_values(&self.values).into_iter().next() {
+            Some((key, message)) => Err(FftvizError::InvalidValue(format!(
+                "{} (from {}): {}",
+                key,
+                self.origin(key)
+                    .map(|s| s.origin(key))
+                    .unwrap_or_else(|| String::from("unknown")),
+                message
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    // One `key: value  # origin` line per setting. Mappings (keybindings)
+    // continue on the following lines.
+    pub fn to_annotated_yaml(&self) -> String {
+        let values = match serde_yaml::to_value(&self.values) {
+            Ok(Value::Mapping(m)) => m,
+            _ => return String::new(),
+        };
+
+        let lines = self
+            .origins
+            .iter()
+            .map(|(key, _)| {
+                let value = values
+                    .get(key.as_str())
+                    .and_then(|v| serde_yaml::to_string(v).ok())
+                    .unwrap_or_default();
+                let value = value.trim_end();
+                if value.contains('\n') {
+                    let nested = value
+                        .lines()
+                        .map(|l| format!("  {}", l))
+                        .collect::<Vec<String>>()
+                        .join("\n");
+                    (format!("{}:", key), Some(nested))
+                } else {
+                    (format!("{}: {}", key, value), None)
+                }
+            })
+            .collect::<Vec<(String, Option<String>)>>();
+
+        let width = lines.iter().map(|(l, _)| l.len()).max().unwrap_or(0);
+        lines
+            .iter()
+            .zip(self.origins.iter())
+            .map(|((line, nested), (key, source))| {
+                let line = format!("{:width$}  # {}", line, source.origin(key), width = width);
+                match nested {
+                    Some(nested) => format!("{}\n{}", line, nested),
+                    None => line,
+                }
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+// Layers `cli` over `env` (FFTVIZ_* env vars), the profile, the user's config
+// file at `user_path`, the system config and the defaults. Config files are
+// skipped unless `use_config_files` is set.
+pub fn resolve_settings(
+    cli: ConfigFFTArgs,
+    env: ConfigFFTArgs,
+    profile: Option<&str>,
+    user_path: Option<&Path>,
+    use_config_files: bool,
+) -> Result<Settings> {
+    let mut layers = vec![(SettingSource::Default, ConfigFFTArgs::default())];
+
+    if use_config_files {
+        if let Some(system_path) = system_config_path() {
+            let system_config = read_config_file(&system_path)?;
+            layers.push((SettingSource::SystemConfig(system_path), system_config.base()));
+        }
+
+        let user_path = user_path.filter(|p| p.is_file());
+        let user_config = match user_path {
+            Some(path) => Some(read_config_file(path)?),
+            None => None,
+        };
+        if let (Some(path), Some(cfg)) = (user_path, &user_config) {
+            layers.push((SettingSource::UserConfig(path.to_path_buf()), cfg.base()));
+        }
+
+        if let Some(name) = profile {
+            let cfg = user_config.ok_or_else(|| {
+                FftvizError::InvalidValue(format!(
+                    "unknown profile \"{}\": there is no config file.",
+                    name
+                ))
+            })?;
+            layers.push((SettingSource::Profile(name.to_string()), profile_chain(&cfg, name)?));
+        }
+    }
+
+    layers.push((SettingSource::Env, env));
+    layers.push((SettingSource::Cli, cli));
+
+    // Highest precedence first
+    layers.reverse();
+    let mut values = ConfigFFTArgs::empty();
+    for (_, layer) in layers.iter() {
+        values = values.inherit(layer);
+    }
+
+    let set_keys = layers
+        .iter()
+        .map(|(source, layer)| (source, layer.set_keys()))
+        .collect::<Vec<_>>();
+    let origins = known_keys()
+        .into_iter()
+        .filter_map(|key| {
+            set_keys
+                .iter()
+                .find(|(_, keys)| keys.contains(&key))
+                .map(|(source, _)| (key, (*source).clone()))
+        })
+        .collect();
+
+    Ok(Settings { values, origins })
+}
@@ -1,18 +1,27 @@
+use crate::systems::recompute_fft::{analysis_settings_changed, FFTRecompute};
+use crate::systems::update_level_meters::{current_loudness_block, LevelMeter};
 use crate::{
-    cli_args_to_fft_args, config_path, parse_cli_args, write_fftargs_to_config,
-    AppState, FFTArgs, FFTState,
+    analyzer_params, band_spacing, config_profile_names, write_fftargs_to_config, AppState,
+    ArgLayers, AudioBuffer, BandSpacing, ChromaView, FFTArgs, FFTState, Loudness, MAX_SPEED,
+    MIN_BANDS, MIN_SPEED,
 };
 use bevy::prelude::*;
 use bevy_egui::egui::Align2;
 use bevy_egui::{egui, EguiContexts};
-use clap::Parser;
 
+#[allow(clippy::too_many_arguments)]
 pub fn ui_example_system(
     mut contexts: EguiContexts,
     fft_state: ResMut<FFTState>,
     mut app_state: ResMut<AppState>,
     mut args: ResMut<FFTArgs>,
+    mut layers: ResMut<ArgLayers>,
+    loudness: Res<Loudness>,
+    audio_buffer: Res<AudioBuffer>,
+    mut recompute: ResMut<FFTRecompute>,
+    mut recompute_pending: Local<bool>,
 ) {
+    let previous_args = args.clone();
     if args.display_gui {
         let window_handle = egui::Window::new("")
             .fixed_size(egui::Vec2 { x: 100.0, y: 100.0 })
@@ -20,6 +29,34 @@ pub fn ui_example_system(
             .collapsible(false);
 
         window_handle.show(contexts.ctx_mut(), |ui| {
+            if !app_state.profiles.is_empty() {
+                let mut selected = args.profile.clone();
+                let profiles = app_state.profiles.clone();
+                ui.horizontal(|ui| {
+                    ui.label("Profile: ");
+                    egui::ComboBox::from_id_source("profile")
+                        .selected_text(selected.clone().unwrap_or(String::from("None")))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut selected, None, "None");
+                            for name in profiles {
+                                ui.selectable_value(&mut selected, Some(name.clone()), name);
+                            }
+                        });
+                });
+                if selected != args.profile {
+                    app_state.display_str = match layers.resolve(selected.clone(), true) {
+                        Ok(new_args) => {
+                            *args = new_args;
+                            args.display_gui = true;
+                            app_state.sink.set_volume(args.volume as f32 / 100.0);
+                            format!("Switched to profile {}.", selected.unwrap_or(String::from("None")))
+                        }
+                        Err(e) => e.to_string(),
+                    };
+                    app_state.display_start_time = fft_state.fft_timer.elapsed().as_secs_f64();
+                }
+            }
+
             ui.checkbox(&mut args.track_name, "Display title: ");
             if args.track_name {
                 ui.horizontal(|ui| {
@@ -52,24 +89,128 @@ pub fn ui_example_system(
                 ui.add(egui::Slider::new(&mut args.border_size, 0..=10).text("value"));
             });
 
+            ui.horizontal(|ui| {
+                ui.label("Smoothness: ");
+                ui.add(egui::Slider::new(&mut args.smoothness, 0..=3).text("value"));
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Frequency resolution: ");
+                ui.add(egui::Slider::new(&mut args.freq_resolution, 10..=300).text("value"));
+            });
+
+            // Ranges keep the analyzer enough bands between min and max, and
+            // always include the current values, which sliders would otherwise
+            // clamp
+            let (min_freq, max_freq) = (args.min_freq, args.max_freq);
+            let spacing = band_spacing(&args.analyzer, &analyzer_params(&audio_buffer, &args))
+                .unwrap_or(BandSpacing::Linear(10.0));
+            let highest_min = spacing.min_freq_below(max_freq, MIN_BANDS).max(0.0);
+            let lowest_max = spacing.max_freq_above(min_freq, MIN_BANDS);
+            ui.horizontal(|ui| {
+                ui.label("Min frequency: ");
+                ui.add(
+                    egui::Slider::new(&mut args.min_freq, 0.0..=highest_min.max(min_freq))
+                        .logarithmic(true)
+                        .suffix(" Hz"),
+                );
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Max frequency: ");
+                ui.add(
+                    egui::Slider::new(
+                        &mut args.max_freq,
+                        lowest_max.min(max_freq)..=max_freq.max(20000.0),
+                    )
+                        .logarithmic(true)
+                        .suffix(" Hz"),
+                );
+            });
+
+            if recompute.is_running() {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label("Recomputing spectrum...");
+                });
+            }
+
+            ui.horizontal(|ui| {
+                ui.label("Speed: ");
+                ui.add(
+                    egui::Slider::new(&mut args.speed, MIN_SPEED..=MAX_SPEED)
+                        .step_by(0.05)
+                        .suffix("x"),
+                )
+                .on_hover_text("Pitch changes with speed");
+            });
+
+            ui.checkbox(&mut args.vectorscope, "Vectorscope: ");
+            ui.checkbox(&mut args.axis, "Frequency axis: ");
+            ui.checkbox(&mut args.tuner, "Tuner: ");
+            ui.horizontal(|ui| {
+                ui.label("Chromagram: ");
+                egui::ComboBox::from_id_source("chroma_view")
+                    .selected_text(format!("{:?}", args.chroma))
+                    .show_ui(ui, |ui| {
+                        for view in [ChromaView::Off, ChromaView::Bars, ChromaView::Wheel] {
+                            ui.selectable_value(&mut args.chroma, view, format!("{:?}", view));
+                        }
+                    });
+            });
+            ui.checkbox(&mut args.piano_axis, "Piano axis: ");
+            ui.checkbox(&mut args.level_meters, "Level meters: ");
+            if args.level_meters {
+                let i = current_loudness_block(&loudness, &fft_state);
+                egui::Grid::new("level_meters").show(ui, |ui| {
+                    for (label, meter, unit) in [
+                        ("Peak", LevelMeter::Peak, "dBFS"),
+                        ("RMS", LevelMeter::Rms, "dBFS"),
+                        ("Momentary", LevelMeter::Momentary, "LUFS"),
+                        ("Short-term", LevelMeter::ShortTerm, "LUFS"),
+                        ("Integrated", LevelMeter::Integrated, "LUFS"),
+                    ] {
+                        ui.label(label);
+                        ui.label(format!("{:.1} {}", meter.value(&loudness, i), unit));
+                        ui.end_row();
+                    }
+                });
+            }
+
             ui.allocate_space(egui::Vec2::new(1.0, 10.0));
             ui.horizontal(|ui| {
                 if ui.button("Save").clicked() {
-                    write_fftargs_to_config(&args);
-                    app_state.display_str = format!("Saved to {:?}", config_path());
+                    app_state.display_str = match write_fftargs_to_config(&args, &layers.resolved) {
+                        Ok(cfg_path) => match &args.profile {
+                            Some(name) => format!("Saved profile {} to {:?}", name, cfg_path),
+                            None => format!("Saved to {:?}", cfg_path),
+                        },
+                        Err(e) => e.to_string(),
+                    };
+                    app_state.profiles = config_profile_names(args.config_path.as_deref());
                     app_state.display_start_time = fft_state.fft_timer.elapsed().as_secs_f64();
                 }
                 if ui.button("Reset").clicked() {
-                    *args = parse_cli_args();
-                    app_state.display_str = String::from("Reset to saved settings.");
+                    app_state.display_str = match layers.resolve(args.profile.clone(), true) {
+                        Ok(new_args) => {
+                            *args = new_args;
+                            args.display_gui = true;
+                            String::from("Reset to saved settings.")
+                        }
+                        Err(e) => e.to_string(),
+                    };
                     app_state.display_start_time = fft_state.fft_timer.elapsed().as_secs_f64();
-                    args.display_gui = true;
                 }
                 if ui.button("Reset to default").clicked() {
-                    *args = cli_args_to_fft_args(crate::args::CLIArgs::parse(), true);
-                    app_state.display_str = String::from("Reset to default settings.");
+                    app_state.display_str = match layers.resolve(None, false) {
+                        Ok(new_args) => {
+                            *args = new_args;
+                            args.display_gui = true;
+                            String::from("Reset to default settings.")
+                        }
+                        Err(e) => e.to_string(),
+                    };
                     app_state.display_start_time = fft_state.fft_timer.elapsed().as_secs_f64();
-                    args.display_gui = true;
                 }
             });
 
@@ -79,6 +220,16 @@ pub fn ui_example_system(
             }
         });
     }
+
+    // Recompute once a slider is let go rather than on every step of a drag.
+    // Profile switches and resets can change analysis settings too.
+    if analysis_settings_changed(&previous_args, &args) {
+        *recompute_pending = true;
+    }
+    if *recompute_pending && !contexts.ctx_mut().input(|i| i.pointer.any_down()) {
+        *recompute_pending = false;
+        recompute.start(&audio_buffer, &args);
+    }
 }
 
 fn color_picker_widget(ui: &mut egui::Ui, color: &mut Color) -> egui::Response {
@@ -92,7 +243,7 @@ fn color_picker_widget(ui: &mut egui::Ui, color: &mut Color) -> egui::Response {
     let res = egui::widgets::color_picker::color_edit_button_rgba(
         ui,
         &mut egui_color,
-        egui::color_picker::Alpha::Opaque,
+        egui::color_picker::Alpha::OnlyBlend,
     );
     let [r, g, b, a] = egui_color.to_srgba_unmultiplied();
     *color = Color::rgba(
@@ -1,40 +1,190 @@
-use crate::{AppState, FFTArgs, FFTState};
+use crate::systems::update_track_end::TrackEnd;
+use crate::{
+    format_time, seek, toggle_fullscreen, toggle_pause, Action, AppState, AudioBuffer, FFTArgs,
+    FFTState, MAX_SPEED, MIN_SPEED, SEEK_STEP, SPEED_STEP, VOLUME_STEP,
+};
 use bevy::{
     app::AppExit,
     prelude::*,
+    render::view::screenshot::ScreenshotManager,
+    window::PrimaryWindow,
 };
+use bevy_egui::EguiContexts;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Panel layouts cycle_layout steps through, as (name, [vectorscope,
+// level_meters, tuner, piano_axis, axis])
+const LAYOUTS: &[(&str, [bool; 5])] = &[
+    ("bars only", [false, false, false, false, false]),
+    ("meters", [false, true, false, false, true]),
+    ("practice", [false, false, true, true, false]),
+    ("studio", [true, true, false, true, true]),
+];
+
+fn cycle_layout(args: &mut FFTArgs) -> &'static str {
+    let current = [
+        args.vectorscope,
+        args.level_meters,
+        args.tuner,
+        args.piano_axis,
+        args.axis,
+    ];
+    let next = LAYOUTS
+        .iter()
+        .position(|(_, panels)| *panels == current)
+        .map_or(0, |i| (i + 1) % LAYOUTS.len());
+    let (name, [vectorscope, level_meters, tuner, piano_axis, axis]) = LAYOUTS[next];
+    args.vectorscope = vectorscope;
+    args.level_meters = level_meters;
+    args.tuner = tuner;
+    args.piano_axis = piano_axis;
+    args.axis = axis;
+    name
+}
 
+#[allow(clippy::too_many_arguments)]
 pub fn get_keyboard_input(
     keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut contexts: EguiContexts,
     mut exit: EventWriter<AppExit>,
     mut app_state: ResMut<AppState>,
     mut fft_state: ResMut<FFTState>,
     mut args: ResMut<FFTArgs>,
+    mut screenshots: ResMut<ScreenshotManager>,
+    mut window: Query<(Entity, &mut Window), With<PrimaryWindow>>,
+    mut track_end: ResMut<TrackEnd>,
+    time: Res<Time>,
+    audio: Res<AudioBuffer>,
 ) {
-    if keyboard_input.just_pressed(KeyCode::KeyQ) {
+    // Keys typed into the config gui are not shortcuts
+    if contexts.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+    let bindings = args.keybindings.clone();
+    let pressed = |action| bindings.just_pressed(action, &keyboard_input);
+
+    if pressed(Action::Quit) {
         exit.send(AppExit);
     }
-    if keyboard_input.just_pressed(KeyCode::KeyE) {
+    if pressed(Action::ToggleGui) {
         args.display_gui = !args.display_gui;
     }
-    if keyboard_input.just_pressed(KeyCode::Space) {
-        app_state.paused = !app_state.paused;
-        if app_state.sink.is_paused() {
-            app_state.sink.play();
-            fft_state.fft_timer.start();
+    if pressed(Action::ToggleHelp) {
+        app_state.show_help = !app_state.show_help;
+    }
+    if pressed(Action::ToggleVectorscope) {
+        args.vectorscope = !args.vectorscope;
+    }
+    if pressed(Action::ToggleLevelMeters) {
+        args.level_meters = !args.level_meters;
+    }
+    if pressed(Action::ToggleTuner) {
+        args.tuner = !args.tuner;
+    }
+    if pressed(Action::TogglePianoAxis) {
+        args.piano_axis = !args.piano_axis;
+    }
+    if pressed(Action::ToggleAxis) {
+        args.axis = !args.axis;
+    }
+    if pressed(Action::CycleChroma) {
+        args.chroma = args.chroma.next();
+    }
+    if pressed(Action::CycleLayout) {
+        app_state.display_str = format!("Layout: {}", cycle_layout(&mut args));
+        app_state.display_start_time = fft_state.fft_timer.elapsed().as_secs_f64();
+    }
+    if pressed(Action::ToggleFullscreen) {
+        if let Ok((_, mut window)) = window.get_single_mut() {
+            toggle_fullscreen(&mut window, args.fullscreen);
+        }
+    }
+    if pressed(Action::PlayPause) {
+        toggle_pause(&mut app_state, &mut fft_state);
+    }
+    for (action, step) in [
+        (Action::SeekForward, SEEK_STEP),
+        (Action::SeekBackward, -SEEK_STEP),
+    ] {
+        if pressed(action) {
+            let position = fft_state.elapsed() + step;
+            if let Err(e) = seek(position, &mut app_state, &mut fft_state, &args, &audio) {
+                app_state.display_str = e.to_string();
+                app_state.display_start_time = fft_state.fft_timer.elapsed().as_secs_f64();
+            }
+        }
+    }
+    if pressed(Action::NextTrack) {
+        let now = time.elapsed_seconds_f64();
+        if !track_end.skip_to_next(&app_state, &mut fft_state, &args, now) {
+            app_state.display_str = String::from("This is the last file.");
+            app_state.display_start_time = fft_state.fft_timer.elapsed().as_secs_f64();
+        }
+    }
+    if pressed(Action::SetLoopStart) {
+        let start = fft_state.elapsed();
+        app_state.loop_start = Some(start);
+        if app_state.loop_end.is_some_and(|end| end <= start) {
+            app_state.loop_end = None;
+        }
+        app_state.display_str = format!("Loop start: {}", format_time(start));
+        app_state.display_start_time = fft_state.fft_timer.elapsed().as_secs_f64();
+    }
+    if pressed(Action::SetLoopEnd) {
+        let end = fft_state.elapsed();
+        let start = app_state.loop_start.unwrap_or(0.0);
+        app_state.display_str = if end > start {
+            app_state.loop_start = Some(start);
+            app_state.loop_end = Some(end);
+            format!("Looping {} - {}", format_time(start), format_time(end))
         } else {
-            app_state.sink.pause();
-            fft_state.fft_timer.stop();
+            String::from("The loop end has to be after its start.")
+        };
+        app_state.display_start_time = fft_state.fft_timer.elapsed().as_secs_f64();
+    }
+    if pressed(Action::ClearLoop) {
+        app_state.loop_start = None;
+        app_state.loop_end = None;
+        app_state.display_str = String::from("Loop cleared.");
+        app_state.display_start_time = fft_state.fft_timer.elapsed().as_secs_f64();
+    }
+    // The new speed is applied by update_frame_counters
+    for (action, speed) in [
+        (Action::SpeedUp, args.speed + SPEED_STEP),
+        (Action::SpeedDown, args.speed - SPEED_STEP),
+        (Action::SpeedReset, 1.0),
+    ] {
+        if pressed(action) {
+            args.speed = ((speed / SPEED_STEP).round() * SPEED_STEP).clamp(MIN_SPEED, MAX_SPEED);
+            app_state.display_str = format!("Speed: {:.2}x", args.speed);
+            app_state.display_start_time = fft_state.fft_timer.elapsed().as_secs_f64();
         }
     }
-    if keyboard_input.just_pressed(KeyCode::ArrowUp) {
-        args.volume += 5;
-        args.volume = args.volume.min(100);
+    if pressed(Action::VolumeUp) {
+        args.volume = (args.volume + VOLUME_STEP).min(100);
         app_state.sink.set_volume(args.volume as f32 / 100.0);
     }
-    if keyboard_input.just_pressed(KeyCode::ArrowDown) {
-        args.volume -= 5;
-        args.volume = args.volume.max(0);
+    if pressed(Action::VolumeDown) {
+        args.volume = args.volume.saturating_sub(VOLUME_STEP);
         app_state.sink.set_volume(args.volume as f32 / 100.0);
     }
+    if pressed(Action::Screenshot) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let path = format!("fftviz-{}.png", timestamp);
+        app_state.display_str = match window
+            .get_single()
+            .map_err(|e| e.to_string())
+            .and_then(|(w, _)| {
+                screenshots
+                    .save_screenshot_to_disk(w, &path)
+                    .map_err(|e| e.to_string())
+            }) {
+            Ok(()) => format!("Saved screenshot to {}", path),
+            Err(e) => format!("Could not save screenshot: {}", e),
+        };
+        app_state.display_start_time = fft_state.fft_timer.elapsed().as_secs_f64();
+    }
 }
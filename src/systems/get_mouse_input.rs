@@ -0,0 +1,98 @@
+use crate::systems::update_seek_bar::SeekBar;
+use crate::*;
+use bevy::input::mouse::{MouseScrollUnit, MouseWheel};
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy_egui::EguiContexts;
+
+// Scroll to change volume, click to pause, double-click for fullscreen,
+// right-click for the config gui and drag the seek bar to seek. Pointer
+// input over egui windows is left to egui.
+#[allow(clippy::too_many_arguments)]
+pub fn get_mouse_input(
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    mut wheel: EventReader<MouseWheel>,
+    mut contexts: EguiContexts,
+    mut window: Query<&mut Window, With<PrimaryWindow>>,
+    time: Res<Time>,
+    mut pending_click: Local<Option<f64>>,
+    mut scrolled: Local<f32>,
+    mut seek_bar: ResMut<SeekBar>,
+    mut app_state: ResMut<AppState>,
+    mut fft_state: ResMut<FFTState>,
+    mut args: ResMut<FFTArgs>,
+    audio: Res<AudioBuffer>,
+) {
+    let Ok(mut window) = window.get_single_mut() else {
+        return;
+    };
+    let now = time.elapsed_seconds_f64();
+    let cursor = window.cursor_position();
+    let ctx = contexts.ctx_mut();
+    let over_egui = ctx.wants_pointer_input() || ctx.is_pointer_over_area();
+
+    // Drags that started on the seek bar keep going over egui windows
+    if let Some(position) = seek_bar.drag_position {
+        if mouse_buttons.pressed(MouseButton::Left) {
+            if let Some(cursor) = cursor {
+                let fraction = (cursor.x / window.width()).clamp(0.0, 1.0) as f64;
+                seek_bar.drag_position = Some(fraction * track_duration(&audio));
+            }
+        } else {
+            seek_bar.drag_position = None;
+            if let Err(e) = seek(position, &mut app_state, &mut fft_state, &args, &audio) {
+                app_state.display_str = e.to_string();
+                app_state.display_start_time = fft_state.fft_timer.elapsed().as_secs_f64();
+            }
+        }
+        wheel.clear();
+        return;
+    }
+
+    seek_bar.hovered =
+        !over_egui && cursor.is_some_and(|c| SeekBar::contains(c.y, window.height(), &args));
+    if over_egui {
+        wheel.clear();
+        *pending_click = None;
+        return;
+    }
+
+    // Whole volume steps, keeping the remainder of smooth (pixel) scrolling
+    for event in wheel.read() {
+        *scrolled += match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / SCROLL_PIXELS_PER_LINE,
+        };
+    }
+    let steps = scrolled.trunc();
+    if steps != 0.0 {
+        *scrolled -= steps;
+        args.volume = (args.volume as i32 + steps as i32 * VOLUME_STEP as i32).clamp(0, 100) as u32;
+        app_state.sink.set_volume(args.volume as f32 / 100.0);
+    }
+
+    if mouse_buttons.just_pressed(MouseButton::Right) {
+        args.display_gui = !args.display_gui;
+    }
+
+    if mouse_buttons.just_pressed(MouseButton::Left) {
+        if seek_bar.hovered {
+            let fraction = cursor.map_or(0.0, |c| (c.x / window.width()).clamp(0.0, 1.0)) as f64;
+            seek_bar.drag_position = Some(fraction * track_duration(&audio));
+            *pending_click = None;
+            return;
+        }
+        // A single click only pauses once it can't become a double click
+        match *pending_click {
+            Some(t) if now - t <= DOUBLE_CLICK_TIME => {
+                *pending_click = None;
+                toggle_fullscreen(&mut window, args.fullscreen);
+            }
+            _ => *pending_click = Some(now),
+        }
+    }
+    if pending_click.is_some_and(|t| now - t > DOUBLE_CLICK_TIME) {
+        *pending_click = None;
+        toggle_pause(&mut app_state, &mut fft_state);
+    }
+}
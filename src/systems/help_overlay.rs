@@ -0,0 +1,37 @@
+use crate::{AppState, FFTArgs, ACTIONS};
+use bevy::prelude::*;
+use bevy_egui::egui::Align2;
+use bevy_egui::{egui, EguiContexts};
+
+// Lists the current keybindings, toggled with the toggle_help action
+pub fn help_overlay(
+    mut contexts: EguiContexts,
+    mut app_state: ResMut<AppState>,
+    args: Res<FFTArgs>,
+) {
+    if !app_state.show_help {
+        return;
+    }
+
+    let mut open = true;
+    egui::Window::new("Keybindings")
+        .open(&mut open)
+        .anchor(Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .collapsible(false)
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            egui::Grid::new("keybindings")
+                .striped(true)
+                .spacing(egui::Vec2::new(20.0, 4.0))
+                .show(ui, |ui| {
+                    for info in ACTIONS {
+                        ui.monospace(args.keybindings.describe(info.action));
+                        ui.label(info.description);
+                        ui.end_row();
+                    }
+                });
+        });
+    if !open {
+        app_state.show_help = false;
+    }
+}
@@ -1,6 +1,21 @@
 pub mod get_keyboard_input;
+pub mod get_mouse_input;
 pub mod egui;
+pub mod help_overlay;
 pub mod startup;
 pub mod update_fft;
 pub mod update_view_settings;
 pub mod update_frame_counters;
+pub mod update_vectorscope;
+pub mod update_level_meters;
+pub mod update_tuner;
+pub mod update_piano_axis;
+pub mod update_axis;
+pub mod update_chroma;
+pub mod recompute_fft;
+pub mod watch_config;
+pub mod update_seek_bar;
+pub mod update_time_label;
+pub mod update_ab_loop;
+pub mod update_track_end;
+pub mod update_track_info;
@@ -0,0 +1,99 @@
+use crate::systems::startup::spawn_bars;
+use crate::*;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy::tasks::futures_lite::future;
+use bevy::tasks::{AsyncComputeTaskPool, Task};
+
+// Spectrum being recomputed in the background after analysis settings changed.
+// The current spectrum keeps playing until the new one is swapped in.
+#[derive(Resource, Default)]
+pub struct FFTRecompute {
+    task: Option<Task<Result<Spectrum>>>,
+}
+
+impl FFTRecompute {
+    // Starts recomputing with `args`, cancelling any recompute in progress
+    pub fn start(&mut self, audio_buffer: &AudioBuffer, args: &FFTArgs) {
+        let audio_buffer = audio_buffer.clone();
+        let args = args.clone();
+        self.task = Some(
+            AsyncComputeTaskPool::get()
+                .spawn(async move { compute_and_preprocess_fft(&audio_buffer, &args) }),
+        );
+    }
+
+    // Drops a recompute in progress, e.g. when its track is replaced
+    pub fn cancel(&mut self) {
+        self.task = None;
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.task.is_some()
+    }
+}
+
+// Whether going from `old` to `new` changes the computed spectrum
+pub fn analysis_settings_changed(old: &FFTArgs, new: &FFTArgs) -> bool {
+    old.smoothness != new.smoothness
+        || old.freq_resolution != new.freq_resolution
+        || old.min_freq != new.min_freq
+        || old.max_freq != new.max_freq
+        || old.analyzer != new.analyzer
+        || old.bins_per_octave != new.bins_per_octave
+}
+
+// Swaps in a finished recompute and respawns the bars for it. The frequency
+// and piano axes follow the new bar frequencies on their own.
+#[allow(clippy::too_many_arguments)]
+pub fn apply_recompute(
+    mut commands: Commands,
+    window: Query<&Window, With<PrimaryWindow>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut recompute: ResMut<FFTRecompute>,
+    mut fft_state: ResMut<FFTState>,
+    mut chroma: ResMut<Chroma>,
+    mut app_state: ResMut<AppState>,
+    args: Res<FFTArgs>,
+) {
+    let Ok(window) = window.get_single() else {
+        return;
+    };
+    let Some(task) = recompute.task.as_mut() else {
+        return;
+    };
+    let Some(result) = future::block_on(future::poll_once(task)) else {
+        return;
+    };
+    recompute.task = None;
+
+    let (fft_vec, bar_freqs, levels, new_chroma) = match result {
+        Ok(x) => x,
+        Err(e) => {
+            app_state.display_str = e.to_string();
+            app_state.display_start_time = fft_state.fft_timer.elapsed().as_secs_f64();
+            return;
+        }
+    };
+
+    for e in fft_state.despawn_handles.drain(..) {
+        commands.entity(e).despawn();
+    }
+    let (mesh_handles, despawn_handles) = spawn_bars(
+        fft_vec[0].len() as u32,
+        window.width(),
+        window.height(),
+        &args,
+        &mut commands,
+        &mut meshes,
+        &mut materials,
+    );
+
+    fft_state.fft = fft_vec;
+    fft_state.bar_freqs = bar_freqs;
+    fft_state.levels = levels;
+    fft_state.curr_bars = mesh_handles;
+    fft_state.despawn_handles = despawn_handles;
+    *chroma = new_chroma;
+}
@@ -1,19 +1,29 @@
-use crate::{FFTArgs, FFTState};
+use crate::{spectrum_width, FFTArgs, FFTState};
 use bevy::sprite::Anchor;
 use bevy::{
     prelude::*,
     sprite::{MaterialMesh2dBundle, Mesh2dHandle},
+    window::PrimaryWindow,
 };
 
-fn spawn_bars(
+// Marks the currently playing label so other text entities are left alone.
+// Its text is filled in by update_track_label.
+#[derive(Component)]
+pub struct TrackNameText;
+
+// Mesh and material of each bar's border and fill, in pairs
+pub type BarHandles = Vec<(Handle<Mesh>, Handle<ColorMaterial>)>;
+
+pub fn spawn_bars(
     num_bars: u32,
     w: f32,
+    h: f32,
     args: &FFTArgs,
     commands: &mut Commands,
     meshes: &mut ResMut<Assets<Mesh>>,
     materials: &mut ResMut<Assets<ColorMaterial>>,
-) -> (Vec<(Handle<Mesh>, Handle<ColorMaterial>)>, Vec<Entity>) {
-    let bar_size = w as f32 / num_bars as f32;
+) -> (BarHandles, Vec<Entity>) {
+    let bar_size = spectrum_width(w, h, args) / num_bars as f32;
     let mut handle_vec = Vec::new();
     let mut despawn_handles = Vec::new();
 
@@ -27,7 +37,7 @@ fn spawn_bars(
                 mesh: Mesh2dHandle(handle1),
                 material: color_handle,
                 transform: Transform::from_xyz(
-                    bar_size * i as f32 + (bar_size / 2.0) - (w / 2.0) as f32,
+                    bar_size * i as f32 + (bar_size / 2.0) - (w / 2.0),
                     0.0,
                     -1.0,
                 ),
@@ -45,7 +55,7 @@ fn spawn_bars(
                 mesh: Mesh2dHandle(handle2),
                 material: color_handle,
                 transform: Transform::from_xyz(
-                    bar_size * i as f32 + (bar_size / 2.0) - (w / 2.0) as f32,
+                    bar_size * i as f32 + (bar_size / 2.0) - (w / 2.0),
                     0.0,
                     0.0,
                 ),
@@ -59,21 +69,21 @@ fn spawn_bars(
 
 pub fn startup(
     mut commands: Commands,
-    mut window: Query<&mut Window>,
+    window: Query<&Window, With<PrimaryWindow>>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
     mut fft_queue: ResMut<FFTState>,
     args: Res<FFTArgs>,
 ) {
-    commands.spawn(Camera2dBundle::default());
-
+    let Ok(window) = window.get_single() else {
+        return;
+    };
     let mut differencing_args = args.clone();
     differencing_args.track_name = !args.track_name;
 
     commands.spawn(differencing_args);
 
-    let w = window.single_mut().width();
-    let h = window.single_mut().height();
+    let (w, h) = (window.width(), window.height());
 
     let text_style = TextStyle {
         font: Default::default(),
@@ -81,24 +91,26 @@ pub fn startup(
         color: args.text_color,
     };
 
-    commands.spawn(Text2dBundle {
-        text: Text::from_section(
-            format!(
-                "Playing: \"{}\"",
-                args.file_path.file_name().unwrap().to_str().unwrap()
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section("", text_style.clone()),
+            transform: Transform::from_xyz(
+                -(w / 2.0) + 10.0,
+                (h / 2.0) - 10.0,
+                0.0,
             ),
-            text_style.clone(),
-        ),
-        transform: Transform::from_xyz(-(w as f32 / 2.0) + 10.0, (h as f32 / 2.0) - 10.0, 0.0),
-        text_anchor: Anchor::TopLeft,
-        ..default()
-    });
+            text_anchor: Anchor::TopLeft,
+            ..default()
+        },
+        TrackNameText,
+    ));
 
     let num_bars = fft_queue.fft[0].len();
 
     let (mesh_handles, despawn_handles) = spawn_bars(
         num_bars as u32,
         w,
+        h,
         &args,
         &mut commands,
         &mut meshes,
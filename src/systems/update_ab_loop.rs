@@ -0,0 +1,25 @@
+use crate::*;
+use bevy::prelude::*;
+
+// Jumps back to the loop start at the loop end, or at the end of the track
+// when no end is set. Runs before the frame counters so that the end of the
+// track is never reached while looping.
+pub fn update_ab_loop(
+    mut app_state: ResMut<AppState>,
+    mut fft_state: ResMut<FFTState>,
+    args: Res<FFTArgs>,
+    audio: Res<AudioBuffer>,
+) {
+    let Some(start) = app_state.loop_start else {
+        return;
+    };
+    let end = app_state.loop_end.unwrap_or(fft_state.end_time());
+
+    if fft_state.elapsed() >= end {
+        if let Err(e) = seek(start, &mut app_state, &mut fft_state, &args, &audio) {
+            app_state.display_str = e.to_string();
+            app_state.display_start_time = fft_state.fft_timer.elapsed().as_secs_f64();
+            app_state.loop_start = None;
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use crate::*;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy::sprite::Anchor;
+use bevy_egui::{egui, EguiContexts};
+
+#[derive(Component)]
+pub struct AxisRoot;
+
+// Fractional bar index a frequency tick sits at
+#[derive(Component)]
+pub struct FrequencyTick(f32);
+
+// Gridline level in dB
+#[derive(Component)]
+pub struct LevelLabel(f32);
+
+// Height above the centre of a bar steady at `db`, None where bars are clamped.
+// Ignores the frequency weighting, which moves bars by up to a fifth.
+fn level_to_height(scale: &LevelScale, db: f32, h: f32) -> Option<f32> {
+    let value = scale.bar_value(db, RESCALING_THRESHOLDS, INTENSITY_RESCALING);
+    let height = value * h / 2.0;
+    (height > h * MIN_BAR_HEIGHT && height < h * MAX_BAR_HEIGHT).then_some(height)
+}
+
+fn format_frequency(f: f32) -> String {
+    match f >= 1000.0 {
+        true => format!("{:.1} kHz", f / 1000.0),
+        false => format!("{:.0} Hz", f),
+    }
+}
+
+fn tick_label(f: f32) -> String {
+    match f >= 1000.0 {
+        true => format!("{}k", f / 1000.0),
+        false => format!("{}", f),
+    }
+}
+
+// Every fractional bar index where the bar frequencies cross `freq`, so mirrored
+// halves both get a tick
+fn tick_positions(bar_freqs: &[f32], freq: f32) -> Vec<f32> {
+    bar_freqs
+        .windows(2)
+        .enumerate()
+        .filter(|(_, f)| (f[0] - freq) * (f[1] - freq) <= 0.0 && f[0] != f[1])
+        .map(|(i, f)| i as f32 + (freq - f[0]) / (f[1] - f[0]))
+        .collect()
+}
+
+// Frequency range covered by bar `i`, halfway to each neighbour
+pub fn bar_frequency_range(bar_freqs: &[f32], i: usize) -> (f32, f32) {
+    let f = bar_freqs[i];
+    let prev = if i > 0 { (bar_freqs[i - 1] + f) / 2.0 } else { f };
+    let next = bar_freqs.get(i + 1).map(|n| (n + f) / 2.0).unwrap_or(f);
+    (prev.min(next), prev.max(next))
+}
+
+// Builds the ticks for the current bar frequencies, and rebuilds them whenever
+// those change, e.g. after a recompute or on the next track
+pub fn spawn_axis(
+    mut commands: Commands,
+    fft_state: Res<FFTState>,
+    args: Res<FFTArgs>,
+    roots: Query<Entity, With<AxisRoot>>,
+    mut built_for: Local<Vec<f32>>,
+) {
+    if *built_for == fft_state.bar_freqs {
+        return;
+    }
+    built_for.clone_from(&fft_state.bar_freqs);
+    for root in &roots {
+        commands.entity(root).despawn_recursive();
+    }
+
+    let text_style = TextStyle {
+        font: Default::default(),
+        font_size: 12.0,
+        color: args.text_color,
+    };
+
+    commands
+        .spawn((
+            SpatialBundle {
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            AxisRoot,
+        ))
+        .with_children(|parent| {
+            for freq in AXIS_TICKS {
+                for pos in tick_positions(&fft_state.bar_freqs, *freq) {
+                    parent.spawn((
+                        Text2dBundle {
+                            text: Text::from_section(tick_label(*freq), text_style.clone()),
+                            text_anchor: Anchor::BottomCenter,
+                            ..default()
+                        },
+                        FrequencyTick(pos),
+                    ));
+                }
+            }
+
+            for db in AXIS_GRID_DB {
+                parent.spawn((
+                    Text2dBundle {
+                        text: Text::from_section(format!("{} dB", db), text_style.clone()),
+                        text_anchor: Anchor::BottomRight,
+                        ..default()
+                    },
+                    LevelLabel(*db),
+                ));
+            }
+        });
+}
+
+#[allow(clippy::type_complexity)]
+pub fn update_axis(
+    mut gizmos: Gizmos,
+    window: Query<&Window, With<PrimaryWindow>>,
+    fft_state: Res<FFTState>,
+    args: Res<FFTArgs>,
+    mut root: Query<&mut Visibility, With<AxisRoot>>,
+    mut ticks: Query<(&FrequencyTick, &mut Transform), Without<LevelLabel>>,
+    mut level_labels: Query<
+        (&LevelLabel, &mut Transform, &mut Visibility),
+        (Without<FrequencyTick>, Without<AxisRoot>),
+    >,
+) {
+    let Ok(mut visibility) = root.get_single_mut() else {
+        return;
+    };
+    if !args.axis {
+        *visibility = Visibility::Hidden;
+        return;
+    }
+    *visibility = Visibility::Visible;
+
+    let Ok(window) = window.get_single() else {
+        return;
+    };
+    let (w, h) = (window.width(), window.height());
+    let spectrum_w = spectrum_width(w, h, &args);
+    let bar_size = spectrum_w / fft_state.bar_freqs.len() as f32;
+    let grid_color = args.text_color.with_a(0.2);
+
+    // Frequency ticks sit along the bottom edge, above the piano axis if shown
+    let bottom = match args.piano_axis {
+        true => -h / 2.0 + PIANO_AXIS_HEIGHT + 18.0,
+        false => -h / 2.0 + 4.0,
+    };
+    for (tick, mut transform) in &mut ticks {
+        let x = bar_size * (tick.0 + 0.5) - w / 2.0;
+        transform.translation = Vec3::new(x, bottom + 8.0, 2.0);
+        gizmos.line_2d(Vec2::new(x, bottom), Vec2::new(x, bottom + 6.0), grid_color);
+    }
+
+    // Level gridlines, mirrored around the centre like the bars. The levels
+    // that land where bars are clamped are left out.
+    for (label, mut transform, mut label_visibility) in &mut level_labels {
+        let Some(y) = level_to_height(&fft_state.levels.scale, label.0, h) else {
+            *label_visibility = Visibility::Hidden;
+            continue;
+        };
+        *label_visibility = Visibility::Inherited;
+        let right = spectrum_w - w / 2.0;
+        transform.translation = Vec3::new(right - 4.0, y + 2.0, 2.0);
+        for y in [y, -y] {
+            gizmos.line_2d(Vec2::new(-w / 2.0, y), Vec2::new(right, y), grid_color);
+        }
+    }
+}
+
+pub fn bar_inspector(
+    mut contexts: EguiContexts,
+    window: Query<&Window, With<PrimaryWindow>>,
+    fft_state: Res<FFTState>,
+    args: Res<FFTArgs>,
+) {
+    if !args.axis {
+        return;
+    }
+
+    let Ok(window) = window.get_single() else {
+        return;
+    };
+    let Some(cursor) = window.cursor_position() else {
+        return;
+    };
+
+    let ctx = contexts.ctx_mut();
+    if ctx.is_pointer_over_area() {
+        return;
+    }
+
+    // Nothing to inspect over the vectorscope column
+    let spectrum_w = spectrum_width(window.width(), window.height(), &args);
+    if cursor.x > spectrum_w {
+        return;
+    }
+
+    let n_bars = fft_state.bar_freqs.len();
+    let i = ((cursor.x / spectrum_w * n_bars as f32) as usize).min(n_bars - 1);
+    let (lo, hi) = bar_frequency_range(&fft_state.bar_freqs, i);
+    let frame = fft_state.fft_frame_counter.min(fft_state.fft.len() - 1);
+    let Some(db) = fft_state.levels.db.get(frame).and_then(|f| f.get(i)) else {
+        return;
+    };
+
+    egui::show_tooltip_at_pointer(ctx, egui::Id::new("bar_inspector"), |ui| {
+        ui.label(format!("{} - {}", format_frequency(lo), format_frequency(hi)));
+        ui.label(format!("Level: {:.1} dB", db));
+    });
+}
@@ -0,0 +1,157 @@
+use crate::*;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy::sprite::Anchor;
+use std::f32::consts::TAU;
+
+#[derive(Component)]
+pub struct ChromaRoot;
+
+// Pitch class shown by a bar or label
+#[derive(Component)]
+pub struct ChromaBar(usize);
+
+#[derive(Component)]
+pub struct ChromaLabel(usize);
+
+#[derive(Component)]
+pub struct ChromaKeyText;
+
+pub fn spawn_chroma(mut commands: Commands, chroma: Res<Chroma>, args: Res<FFTArgs>) {
+    let text_style = TextStyle {
+        font: Default::default(),
+        font_size: 12.0,
+        color: args.text_color,
+    };
+
+    commands
+        .spawn((
+            SpatialBundle {
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            ChromaRoot,
+        ))
+        .with_children(|parent| {
+            for (pc, name) in NOTE_NAMES.iter().enumerate() {
+                parent.spawn((
+                    SpriteBundle {
+                        sprite: Sprite {
+                            color: args.bar_color,
+                            anchor: Anchor::BottomCenter,
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    ChromaBar(pc),
+                ));
+                parent.spawn((
+                    Text2dBundle {
+                        text: Text::from_section(*name, text_style.clone()),
+                        ..default()
+                    },
+                    ChromaLabel(pc),
+                ));
+            }
+
+            parent.spawn((
+                Text2dBundle {
+                    text: Text::from_section(
+                        format!("Key: {}", chroma.key),
+                        text_style.clone(),
+                    ),
+                    text_anchor: Anchor::BottomCenter,
+                    ..default()
+                },
+                ChromaKeyText,
+            ));
+        });
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn update_chroma(
+    mut gizmos: Gizmos,
+    window: Query<&Window, With<PrimaryWindow>>,
+    chroma: Res<Chroma>,
+    fft_state: Res<FFTState>,
+    args: Res<FFTArgs>,
+    mut root: Query<(&mut Transform, &mut Visibility), With<ChromaRoot>>,
+    mut bars: Query<
+        (&ChromaBar, &mut Sprite, &mut Transform, &mut Visibility),
+        Without<ChromaRoot>,
+    >,
+    mut labels: Query<
+        (&ChromaLabel, &mut Transform),
+        (Without<ChromaRoot>, Without<ChromaBar>),
+    >,
+    mut key_text: Query<
+        &mut Transform,
+        (With<ChromaKeyText>, Without<ChromaRoot>, Without<ChromaBar>, Without<ChromaLabel>),
+    >,
+) {
+    let Ok((mut root_transform, mut root_visibility)) = root.get_single_mut() else {
+        return;
+    };
+    if args.chroma == ChromaView::Off {
+        *root_visibility = Visibility::Hidden;
+        return;
+    }
+    *root_visibility = Visibility::Visible;
+
+    // Panel in the top right corner, laid out around its centre
+    let Ok(window) = window.get_single() else {
+        return;
+    };
+    let (w, h) = (window.width(), window.height());
+    let size = w.min(h) * CHROMA_SIZE;
+    let half = size / 2.0;
+    let center = Vec2::new(w / 2.0 - half - 10.0, h / 2.0 - half - 30.0);
+    root_transform.translation = center.extend(1.0);
+
+    let frame = &chroma.frames[fft_state.fft_frame_counter.min(chroma.frames.len() - 1)];
+    let Ok(mut key_transform) = key_text.get_single_mut() else {
+        return;
+    };
+    key_transform.translation = Vec3::new(0.0, half + 4.0, 0.0);
+
+    match args.chroma {
+        ChromaView::Bars => {
+            let bar_width = size / 12.0;
+            for (bar, mut sprite, mut transform, mut visibility) in &mut bars {
+                *visibility = Visibility::Inherited;
+                sprite.color = args.bar_color;
+                sprite.custom_size = Some(Vec2::new(bar_width - 2.0, (size - 16.0) * frame[bar.0]));
+                transform.translation =
+                    Vec3::new(bar_width * (bar.0 as f32 + 0.5) - half, 16.0 - half, 0.0);
+            }
+            for (label, mut transform) in &mut labels {
+                transform.translation =
+                    Vec3::new(bar_width * (label.0 as f32 + 0.5) - half, 8.0 - half, 0.0);
+            }
+        }
+        ChromaView::Wheel => {
+            for (_, _, _, mut visibility) in &mut bars {
+                *visibility = Visibility::Hidden;
+            }
+
+            // Spokes in circle-of-fifths order, clockwise from the top
+            let radius = half - 14.0;
+            let grid_color = args.text_color.with_a(0.3);
+            gizmos.circle_2d(center, radius, grid_color);
+            for (i, pc) in CIRCLE_OF_FIFTHS.iter().enumerate() {
+                let angle = TAU * i as f32 / 12.0;
+                let dir = Vec2::new(angle.sin(), angle.cos());
+                let tip = center + dir * radius * frame[*pc];
+                gizmos.line_2d(center, tip, args.bar_color);
+                gizmos.circle_2d(tip, 3.0, args.bar_color);
+
+                for (label, mut transform) in &mut labels {
+                    if label.0 == *pc {
+                        transform.translation = (dir * (radius + 8.0)).extend(0.0);
+                    }
+                }
+            }
+        }
+        ChromaView::Off => {}
+    }
+}
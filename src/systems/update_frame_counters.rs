@@ -1,8 +1,18 @@
 use crate::*;
 use bevy::prelude::*;
 
-pub fn update_frame_counters(mut fft_state: ResMut<FFTState>) {
-    let elapsed_time = fft_state.fft_timer.elapsed().as_secs_f64();
+pub fn update_frame_counters(
+    mut fft_state: ResMut<FFTState>,
+    mut app_state: ResMut<AppState>,
+    args: Res<FFTArgs>,
+) {
+    // The clock runs at the playback speed, so frames stay in sync with the audio
+    if args.speed as f64 != fft_state.speed {
+        set_speed(args.speed, &mut app_state, &mut fft_state);
+    }
+
+    // Frames stop at the end of the track, see update_track_end
+    let elapsed_time = fft_state.elapsed().min(fft_state.end_time());
     fft_state.fft_frame_counter = (elapsed_time / TIME_BETWEEN_FFT_FRAMES) as usize;
     fft_state.total_frame_counter = (elapsed_time / TIME_BETWEEN_FRAMES) as usize;
 }
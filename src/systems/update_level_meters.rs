@@ -0,0 +1,186 @@
+use crate::loudness::BLOCK_DURATION;
+use crate::*;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy::sprite::Anchor;
+
+#[derive(Component)]
+pub struct LevelMeterRoot;
+
+#[derive(Component, Clone, Copy, PartialEq)]
+pub enum LevelMeter {
+    Peak,
+    Rms,
+    Momentary,
+    ShortTerm,
+    Integrated,
+}
+
+#[derive(Component, Clone, Copy, PartialEq)]
+pub enum MeterPart {
+    Background,
+    Fill,
+    PeakHold,
+    Clip,
+}
+
+impl LevelMeter {
+    const ALL: [LevelMeter; 5] = [
+        LevelMeter::Peak,
+        LevelMeter::Rms,
+        LevelMeter::Momentary,
+        LevelMeter::ShortTerm,
+        LevelMeter::Integrated,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            LevelMeter::Peak => "PK",
+            LevelMeter::Rms => "RMS",
+            LevelMeter::Momentary => "M",
+            LevelMeter::ShortTerm => "S",
+            LevelMeter::Integrated => "I",
+        }
+    }
+
+    pub fn value(&self, loudness: &Loudness, i: usize) -> f32 {
+        match self {
+            LevelMeter::Peak => loudness.peak[i],
+            LevelMeter::Rms => loudness.rms[i],
+            LevelMeter::Momentary => loudness.momentary[i],
+            LevelMeter::ShortTerm => loudness.short_term[i],
+            LevelMeter::Integrated => loudness.integrated[i],
+        }
+    }
+}
+
+// Index of the loudness block at the current playback position
+pub fn current_loudness_block(loudness: &Loudness, fft_state: &FFTState) -> usize {
+    let elapsed = fft_state.elapsed();
+    ((elapsed / BLOCK_DURATION) as usize).min(loudness.num_blocks().saturating_sub(1))
+}
+
+fn level_to_height(level: f32) -> f32 {
+    ((level - LEVEL_METER_FLOOR) / -LEVEL_METER_FLOOR).clamp(0.0, 1.0)
+}
+
+fn level_color(level: f32) -> Color {
+    match level {
+        l if l >= -6.0 => Color::RED,
+        l if l >= -18.0 => Color::YELLOW,
+        _ => Color::GREEN,
+    }
+}
+
+pub fn spawn_level_meters(mut commands: Commands, args: Res<FFTArgs>) {
+    let visibility = match args.level_meters && !args.overlay {
+        true => Visibility::Visible,
+        false => Visibility::Hidden,
+    };
+
+    let text_style = TextStyle {
+        font: Default::default(),
+        font_size: 12.0,
+        color: args.text_color,
+    };
+
+    commands
+        .spawn((SpatialBundle { visibility, ..default() }, LevelMeterRoot))
+        .with_children(|parent| {
+            for (i, meter) in LevelMeter::ALL.iter().enumerate() {
+                let x = i as f32 * (LEVEL_METER_WIDTH + LEVEL_METER_GAP) + LEVEL_METER_WIDTH / 2.0;
+                let mut parts = vec![
+                    (MeterPart::Background, Color::rgba(1.0, 1.0, 1.0, 0.1), 1.0),
+                    (MeterPart::Fill, Color::GREEN, 2.0),
+                ];
+                if *meter == LevelMeter::Peak {
+                    parts.push((MeterPart::PeakHold, Color::WHITE, 3.0));
+                    parts.push((MeterPart::Clip, Color::rgba(1.0, 0.0, 0.0, 0.2), 3.0));
+                }
+
+                for (part, color, z) in parts {
+                    parent.spawn((
+                        SpriteBundle {
+                            sprite: Sprite {
+                                color,
+                                custom_size: Some(Vec2::new(LEVEL_METER_WIDTH, 0.0)),
+                                anchor: Anchor::BottomCenter,
+                                ..default()
+                            },
+                            transform: Transform::from_xyz(x, 0.0, z),
+                            ..default()
+                        },
+                        *meter,
+                        part,
+                    ));
+                }
+
+                parent.spawn(Text2dBundle {
+                    text: Text::from_section(meter.label(), text_style.clone()),
+                    transform: Transform::from_xyz(x, -4.0, 3.0),
+                    text_anchor: Anchor::TopCenter,
+                    ..default()
+                });
+            }
+        });
+}
+
+pub fn update_level_meters(
+    window: Query<&Window, With<PrimaryWindow>>,
+    loudness: Res<Loudness>,
+    fft_state: Res<FFTState>,
+    args: Res<FFTArgs>,
+    mut root: Query<(&mut Transform, &mut Visibility), With<LevelMeterRoot>>,
+    mut parts: Query<
+        (&LevelMeter, &MeterPart, &mut Sprite, &mut Transform),
+        Without<LevelMeterRoot>,
+    >,
+) {
+    let Ok((mut root_transform, mut visibility)) = root.get_single_mut() else {
+        return;
+    };
+    if !args.level_meters || args.overlay || loudness.num_blocks() == 0 {
+        *visibility = Visibility::Hidden;
+        return;
+    }
+    *visibility = Visibility::Visible;
+
+    // Keep meters pinned to the left edge through resizes
+    let Ok(window) = window.get_single() else {
+        return;
+    };
+    let meter_height = window.height() * LEVEL_METER_HEIGHT;
+    root_transform.translation.x = 10.0 - window.width() / 2.0;
+    root_transform.translation.y = -meter_height / 2.0;
+
+    let i = current_loudness_block(&loudness, &fft_state);
+    let peak_hold = loudness.peak_hold(i, (PEAK_HOLD_TIME / BLOCK_DURATION) as usize);
+
+    for (meter, part, mut sprite, mut transform) in &mut parts {
+        match part {
+            MeterPart::Background => {
+                sprite.custom_size = Some(Vec2::new(LEVEL_METER_WIDTH, meter_height));
+            }
+            MeterPart::Fill => {
+                let level = meter.value(&loudness, i);
+                sprite.custom_size = Some(Vec2::new(
+                    LEVEL_METER_WIDTH,
+                    meter_height * level_to_height(level),
+                ));
+                sprite.color = level_color(level);
+            }
+            MeterPart::PeakHold => {
+                sprite.custom_size = Some(Vec2::new(LEVEL_METER_WIDTH, 2.0));
+                transform.translation.y = meter_height * level_to_height(peak_hold);
+            }
+            MeterPart::Clip => {
+                sprite.custom_size = Some(Vec2::new(LEVEL_METER_WIDTH, LEVEL_METER_WIDTH));
+                transform.translation.y = meter_height + 4.0;
+                sprite.color = match peak_hold >= CLIP_LEVEL {
+                    true => Color::RED,
+                    false => Color::rgba(1.0, 0.0, 0.0, 0.2),
+                };
+            }
+        }
+    }
+}
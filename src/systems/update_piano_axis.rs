@@ -0,0 +1,130 @@
+use crate::*;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy::sprite::Anchor;
+
+#[derive(Component)]
+pub struct PianoAxisRoot;
+
+// Index of the bar a key or label sits under
+#[derive(Component)]
+pub struct PianoKey(usize);
+
+#[derive(Component)]
+pub struct PianoLabel(usize);
+
+// Rebuilt whenever the bar frequencies change, like the frequency axis
+pub fn spawn_piano_axis(
+    mut commands: Commands,
+    fft_state: Res<FFTState>,
+    args: Res<FFTArgs>,
+    roots: Query<Entity, With<PianoAxisRoot>>,
+    mut built_for: Local<Vec<f32>>,
+) {
+    if *built_for == fft_state.bar_freqs {
+        return;
+    }
+    built_for.clone_from(&fft_state.bar_freqs);
+    for root in &roots {
+        commands.entity(root).despawn_recursive();
+    }
+
+    let text_style = TextStyle {
+        font: Default::default(),
+        font_size: 12.0,
+        color: args.text_color,
+    };
+
+    commands
+        .spawn((
+            SpatialBundle {
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            PianoAxisRoot,
+        ))
+        .with_children(|parent| {
+            let mut prev_midi = None;
+            for (i, freq) in fft_state.bar_freqs.iter().enumerate() {
+                // Bars below the audible range get no key
+                let midi = match *freq > 20.0 {
+                    true => Some(frequency_to_midi(*freq).round() as i32),
+                    false => None,
+                };
+                let color = match midi {
+                    Some(m) if is_sharp(m) => Color::rgb(0.15, 0.15, 0.15),
+                    Some(_) => Color::rgb(0.9, 0.9, 0.9),
+                    None => Color::NONE,
+                };
+
+                parent.spawn((
+                    SpriteBundle {
+                        sprite: Sprite {
+                            color,
+                            anchor: Anchor::BottomCenter,
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    PianoKey(i),
+                ));
+
+                // Label the first bar of every C
+                if let Some(m) = midi {
+                    if m.rem_euclid(12) == 0 && prev_midi != midi {
+                        parent.spawn((
+                            Text2dBundle {
+                                text: Text::from_section(
+                                    format!("C{}", m.div_euclid(12) - 1),
+                                    text_style.clone(),
+                                ),
+                                text_anchor: Anchor::BottomCenter,
+                                ..default()
+                            },
+                            PianoLabel(i),
+                        ));
+                    }
+                }
+                prev_midi = midi;
+            }
+        });
+}
+
+#[allow(clippy::type_complexity)]
+pub fn update_piano_axis(
+    window: Query<&Window, With<PrimaryWindow>>,
+    fft_state: Res<FFTState>,
+    args: Res<FFTArgs>,
+    mut root: Query<(&mut Transform, &mut Visibility), With<PianoAxisRoot>>,
+    mut keys: Query<(&PianoKey, &mut Sprite, &mut Transform), Without<PianoAxisRoot>>,
+    mut labels: Query<
+        (&PianoLabel, &mut Transform),
+        (Without<PianoAxisRoot>, Without<PianoKey>),
+    >,
+) {
+    let Ok((mut root_transform, mut visibility)) = root.get_single_mut() else {
+        return;
+    };
+    if !args.piano_axis {
+        *visibility = Visibility::Hidden;
+        return;
+    }
+    *visibility = Visibility::Visible;
+
+    // Keys follow the bar layout through resizes
+    let Ok(window) = window.get_single() else {
+        return;
+    };
+    let (w, h) = (window.width(), window.height());
+    let bar_size = spectrum_width(w, h, &args) / fft_state.bar_freqs.len() as f32;
+    root_transform.translation = Vec3::new(-w / 2.0, -h / 2.0, 1.0);
+
+    for (key, mut sprite, mut transform) in &mut keys {
+        sprite.custom_size = Some(Vec2::new(bar_size, PIANO_AXIS_HEIGHT));
+        transform.translation.x = bar_size * key.0 as f32 + bar_size / 2.0;
+    }
+    for (label, mut transform) in &mut labels {
+        transform.translation.x = bar_size * label.0 as f32 + bar_size / 2.0;
+        transform.translation.y = PIANO_AXIS_HEIGHT + 2.0;
+    }
+}
@@ -0,0 +1,110 @@
+use crate::*;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy::sprite::Anchor;
+
+#[derive(Component, Clone, Copy, PartialEq)]
+pub enum SeekBarPart {
+    Track,
+    Fill,
+    // A-B loop range
+    Loop,
+}
+
+// Pointer state of the seek bar along the bottom (or, with
+// `--progress-bar top`, the top) edge of the window
+#[derive(Resource, Default)]
+pub struct SeekBar {
+    pub hovered: bool,
+    // Position in seconds the bar is being dragged to, applied on release
+    pub drag_position: Option<f64>,
+}
+
+impl SeekBar {
+    // Whether the cursor at `y` (from the top of a window `height` tall) is
+    // over the seek bar's hover area
+    pub fn contains(y: f32, height: f32, args: &FFTArgs) -> bool {
+        match args.progress_bar {
+            ProgressBar::Top => y <= SEEK_BAR_HOVER_HEIGHT,
+            _ => y >= height - SEEK_BAR_HOVER_HEIGHT,
+        }
+    }
+}
+
+pub fn spawn_seek_bar(mut commands: Commands, args: Res<FFTArgs>) {
+    for (part, color, z) in [
+        (SeekBarPart::Track, Color::rgba(1.0, 1.0, 1.0, 0.15), 10.0),
+        (SeekBarPart::Fill, args.progress_bar_color, 11.0),
+        (SeekBarPart::Loop, Color::rgba(1.0, 1.0, 0.0, 0.35), 12.0),
+    ] {
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color,
+                    custom_size: Some(Vec2::new(0.0, args.progress_bar_thickness)),
+                    anchor: Anchor::BottomLeft,
+                    ..default()
+                },
+                transform: Transform::from_xyz(0.0, 0.0, z),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            part,
+        ));
+    }
+}
+
+// Shows the seek bar while the cursor is near its edge of the window or the
+// bar is being dragged, with the A-B loop range marked on it. With
+// --progress-bar, the played part stays visible as a progress bar.
+pub fn update_seek_bar(
+    window: Query<&Window, With<PrimaryWindow>>,
+    seek_bar: Res<SeekBar>,
+    fft_state: Res<FFTState>,
+    audio: Res<AudioBuffer>,
+    args: Res<FFTArgs>,
+    app_state: Res<AppState>,
+    mut parts: Query<(&SeekBarPart, &mut Sprite, &mut Transform, &mut Visibility)>,
+) {
+    let Ok(window) = window.get_single() else {
+        return;
+    };
+    let (w, h) = (window.width(), window.height());
+    let position = seek_bar.drag_position.unwrap_or(fft_state.elapsed());
+    let duration = track_duration(&audio);
+    let fraction = |t: f64| (t / duration).clamp(0.0, 1.0) as f32;
+    let loop_range = app_state
+        .loop_start
+        .map(|start| (fraction(start), fraction(app_state.loop_end.unwrap_or(duration))));
+    let (anchor, y) = match args.progress_bar {
+        ProgressBar::Top => (Anchor::TopLeft, h / 2.0),
+        _ => (Anchor::BottomLeft, -h / 2.0),
+    };
+
+    // The overlay only shows the bars
+    let active = (seek_bar.hovered || seek_bar.drag_position.is_some()) && !args.overlay;
+    let progress = args.progress_bar != ProgressBar::Off && !args.overlay;
+    for (part, mut sprite, mut transform, mut visibility) in parts.iter_mut() {
+        let (start, end) = match part {
+            SeekBarPart::Track => (0.0, 1.0),
+            SeekBarPart::Fill => {
+                sprite.color = args.progress_bar_color;
+                (0.0, fraction(position))
+            }
+            SeekBarPart::Loop => loop_range.unwrap_or((0.0, 0.0)),
+        };
+        let shown = match part {
+            SeekBarPart::Track => active,
+            SeekBarPart::Fill => active || progress,
+            SeekBarPart::Loop => active && loop_range.is_some(),
+        };
+        *visibility = match shown {
+            true => Visibility::Visible,
+            false => Visibility::Hidden,
+        };
+        sprite.custom_size = Some(Vec2::new(w * (end - start), args.progress_bar_thickness));
+        sprite.anchor = anchor;
+        transform.translation.x = -w / 2.0 + w * start;
+        transform.translation.y = y;
+    }
+}
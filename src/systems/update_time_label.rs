@@ -0,0 +1,73 @@
+use crate::*;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy::sprite::Anchor;
+
+// "elapsed / -remaining" label in the bottom right corner
+#[derive(Component)]
+pub struct TimeLabel;
+
+// Position of the time label, which sits above the progress bar when that
+// runs along the bottom edge
+pub fn time_label_position(args: &FFTArgs, w: f32, h: f32) -> Vec3 {
+    let offset = match args.progress_bar {
+        ProgressBar::Bottom => args.progress_bar_thickness,
+        _ => 0.0,
+    };
+    Vec3::new(w / 2.0 - 10.0, -h / 2.0 + 10.0 + offset, 9.0)
+}
+
+pub fn spawn_time_label(mut commands: Commands, args: Res<FFTArgs>) {
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font: Default::default(),
+                    font_size: args.font_size as f32,
+                    color: args.text_color,
+                },
+            ),
+            text_anchor: Anchor::BottomRight,
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        TimeLabel,
+    ));
+}
+
+// Updates the time label and keeps it in the corner. The progress bar is
+// drawn by update_seek_bar.
+pub fn update_time_label(
+    window: Query<&Window, With<PrimaryWindow>>,
+    fft_state: Res<FFTState>,
+    args: Res<FFTArgs>,
+    audio: Res<AudioBuffer>,
+    mut label_query: Query<(&mut Text, &mut Transform, &mut Visibility), With<TimeLabel>>,
+) {
+    let Ok(window) = window.get_single() else {
+        return;
+    };
+    let position = time_label_position(&args, window.width(), window.height());
+    let duration = track_duration(&audio);
+    let elapsed = fft_state.elapsed().clamp(0.0, duration);
+    let label = format!("{} / -{}", format_time(elapsed), format_time(duration - elapsed));
+    for (mut text, mut transform, mut visibility) in &mut label_query {
+        transform.translation = position;
+        *visibility = match args.time_label && !args.overlay {
+            true => Visibility::Visible,
+            false => Visibility::Hidden,
+        };
+        // Text is laid out again whenever it is changed
+        let section = &text.sections[0];
+        if section.value != label
+            || section.style.color != args.text_color
+            || section.style.font_size != args.font_size as f32
+        {
+            let section = &mut text.sections[0];
+            section.value = label.clone();
+            section.style.color = args.text_color;
+            section.style.font_size = args.font_size as f32;
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use crate::systems::recompute_fft::FFTRecompute;
+use crate::systems::startup::spawn_bars;
+use crate::*;
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy::tasks::futures_lite::future;
+use bevy::tasks::{AsyncComputeTaskPool, Task};
+
+// State of --on-end once the end of a track is reached
+#[derive(Resource, Default)]
+pub struct TrackEnd {
+    // Time (bevy's, in seconds) the bars started fading out
+    fade_start: Option<f64>,
+    // Paused on the last frame by OnEnd::Hold
+    held: bool,
+    // Playlist index of the next track, and the task decoding and analyzing
+    // it while fading out. Without one, the app exits once the fade is done.
+    next: Option<(usize, Task<Result<LoadedTrack>>)>,
+}
+
+impl TrackEnd {
+    // Stops the bars and starts fading them out, at bevy time `now`
+    fn fade_out(&mut self, fft_state: &mut FFTState, now: f64) {
+        fft_state.fft_timer.stop();
+        self.fade_start = Some(now);
+    }
+
+    // Starts decoding and analyzing playlist track `index` in the background
+    fn load(&mut self, index: usize, args: &FFTArgs) {
+        let path = args.playlist[index].clone();
+        let args = args.clone();
+        let task = AsyncComputeTaskPool::get().spawn(async move { load_track(&path, &args) });
+        self.next = Some((index, task));
+    }
+
+    // Fades out the current track while the next file loads, like the end of
+    // a track with --on-end next. False on the last file.
+    pub fn skip_to_next(
+        &mut self,
+        app_state: &AppState,
+        fft_state: &mut FFTState,
+        args: &FFTArgs,
+        now: f64,
+    ) -> bool {
+        let index = app_state.track_index + 1;
+        if index >= args.playlist.len() {
+            return false;
+        }
+        if self.fade_start.is_none() {
+            self.held = false;
+            self.fade_out(fft_state, now);
+            self.load(index, args);
+        }
+        true
+    }
+}
+
+fn show_error(e: FftvizError, app_state: &mut AppState, fft_state: &FFTState) {
+    app_state.display_str = e.to_string();
+    app_state.display_start_time = fft_state.fft_timer.elapsed().as_secs_f64();
+}
+
+// Applies args.on_end at the end of the track. update_ab_loop takes over
+// while a loop is set. Runs before the frame counters, like update_ab_loop.
+pub fn update_track_end(
+    time: Res<Time>,
+    mut track_end: ResMut<TrackEnd>,
+    mut exit: EventWriter<AppExit>,
+    mut app_state: ResMut<AppState>,
+    mut fft_state: ResMut<FFTState>,
+    args: Res<FFTArgs>,
+    audio: Res<AudioBuffer>,
+) {
+    let now = time.elapsed_seconds_f64();
+    if let Some(start) = track_end.fade_start {
+        fft_state.fade = (1.0 - (now - start) / FADE_OUT_TIME).max(0.0) as f32;
+        // Skipping mid-track fades the audio out along with the bars
+        app_state.sink.set_volume(args.volume as f32 / 100.0 * fft_state.fade);
+        // Otherwise apply_next_track takes over once the next track is ready
+        if fft_state.fade == 0.0 && track_end.next.is_none() {
+            exit.send(AppExit);
+        }
+        return;
+    }
+
+    let at_end = fft_state.elapsed() >= fft_state.end_time();
+    if track_end.held {
+        if app_state.paused && at_end {
+            return;
+        }
+        // Resuming from the last frame starts the track over
+        track_end.held = false;
+        if at_end {
+            if let Err(e) = seek(0.0, &mut app_state, &mut fft_state, &args, &audio) {
+                show_error(e, &mut app_state, &fft_state);
+            }
+        }
+        return;
+    }
+    if !at_end || app_state.loop_start.is_some() {
+        return;
+    }
+
+    match args.on_end {
+        OnEnd::Loop => {
+            if let Err(e) = seek(0.0, &mut app_state, &mut fft_state, &args, &audio) {
+                show_error(e, &mut app_state, &fft_state);
+            }
+        }
+        OnEnd::Hold => {
+            if !app_state.paused {
+                toggle_pause(&mut app_state, &mut fft_state);
+            }
+            track_end.held = true;
+        }
+        OnEnd::Quit => track_end.fade_out(&mut fft_state, now),
+        OnEnd::Next => {
+            // After the last file, the app exits once the bars have faded out
+            if !track_end.skip_to_next(&app_state, &mut fft_state, &args, now) {
+                track_end.fade_out(&mut fft_state, now);
+            }
+        }
+    }
+}
+
+// Swaps in the next track once the bars have faded out and it is loaded
+#[allow(clippy::too_many_arguments)]
+pub fn apply_next_track(
+    mut commands: Commands,
+    mut window: Query<&mut Window, With<PrimaryWindow>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut track_end: ResMut<TrackEnd>,
+    mut recompute: ResMut<FFTRecompute>,
+    mut fft_state: ResMut<FFTState>,
+    mut app_state: ResMut<AppState>,
+    mut audio: ResMut<AudioBuffer>,
+    mut loudness: ResMut<Loudness>,
+    mut chroma: ResMut<Chroma>,
+    mut info: ResMut<TrackInfo>,
+    args: Res<FFTArgs>,
+) {
+    let Ok(mut window) = window.get_single_mut() else {
+        return;
+    };
+    if fft_state.fade > 0.0 {
+        return;
+    }
+    let Some((index, task)) = track_end.next.as_mut() else {
+        return;
+    };
+    let Some(result) = future::block_on(future::poll_once(task)) else {
+        return;
+    };
+    let index = *index;
+    track_end.next = None;
+
+    // A file that can't be played is reported and skipped. update_track_end
+    // exits if it was the last one.
+    let loaded = result.and_then(|track| {
+        let sink = rodio::Sink::try_new(&app_state.stream_handle)?;
+        Ok((track, sink))
+    });
+    let (track, sink) = match loaded {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            show_error(e, &mut app_state, &fft_state);
+            if index + 1 < args.playlist.len() {
+                track_end.load(index + 1, &args);
+            }
+            return;
+        }
+    };
+    sink.set_volume(args.volume as f32 / 100.0);
+    sink.set_speed(fft_state.speed as f32);
+    sink.append(track.audio_buffer.source_from(0.0));
+
+    for e in fft_state.despawn_handles.drain(..) {
+        commands.entity(e).despawn();
+    }
+    let (mesh_handles, despawn_handles) = spawn_bars(
+        track.fft_vec[0].len() as u32,
+        window.width(),
+        window.height(),
+        &args,
+        &mut commands,
+        &mut meshes,
+        &mut materials,
+    );
+
+    // A recompute in progress is for the previous track
+    recompute.cancel();
+    fft_state.fft = track.fft_vec;
+    fft_state.bar_freqs = track.bar_freqs;
+    fft_state.levels = track.levels;
+    fft_state.curr_bars = mesh_handles;
+    fft_state.despawn_handles = despawn_handles;
+    fft_state.fade = 1.0;
+    *audio = track.audio_buffer;
+    *loudness = track.loudness;
+    *chroma = track.chroma;
+    *info = track.info;
+
+    // Pausing while fading out keeps the next track paused
+    if app_state.paused {
+        sink.pause();
+    } else {
+        fft_state.fft_timer.start();
+    }
+    fft_state.seek_offset = -fft_state.fft_timer.elapsed().as_secs_f64() * fft_state.speed;
+    app_state.sink = sink;
+    app_state.track_index = index;
+    app_state.loop_start = None;
+    app_state.loop_end = None;
+    track_end.fade_start = None;
+
+    // args.file_path stays the first track, as settings reloads reset it
+    window.title = window_title(&args.playlist[app_state.track_index]);
+}
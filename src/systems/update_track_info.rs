@@ -0,0 +1,97 @@
+use crate::systems::startup::TrackNameText;
+use crate::*;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy::sprite::Anchor;
+
+// Cover art sprite, with the cover's width over its height
+#[derive(Component)]
+pub struct AlbumArtSprite {
+    aspect: f32,
+}
+
+// Fills in args.title_template, only touching the text when it changes
+pub fn update_track_label(
+    mut text_query: Query<&mut Text, With<TrackNameText>>,
+    info: Res<TrackInfo>,
+    args: Res<FFTArgs>,
+    fft_state: Res<FFTState>,
+    audio: Res<AudioBuffer>,
+) {
+    let duration = track_duration(&audio);
+    let label = info.format(&args.title_template, fft_state.elapsed().min(duration), duration);
+    for mut text in &mut text_query {
+        if text.sections[0].value != label {
+            text.sections[0].value = label.clone();
+        }
+    }
+}
+
+// Shows the cover the way args.album_art asks, respawning it when the track
+// or the setting changes and keeping it sized to the window
+pub fn update_album_art(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    info: Res<TrackInfo>,
+    args: Res<FFTArgs>,
+    mut shown: Local<Option<AlbumArt>>,
+    mut sprites: Query<(Entity, &AlbumArtSprite, &mut Sprite, &mut Transform, &mut Visibility)>,
+) {
+    if info.is_changed() || *shown != Some(args.album_art) {
+        *shown = Some(args.album_art);
+        for (e, ..) in &sprites {
+            commands.entity(e).despawn();
+        }
+        let Some(cover) = &info.cover else {
+            return;
+        };
+        let (image, color) = match args.album_art {
+            // The overlay only shows the bars
+            _ if args.overlay => return,
+            AlbumArt::Off => return,
+            AlbumArt::Sprite => (cover.clone(), Color::WHITE),
+            AlbumArt::Background => (
+                blurred_cover(cover).unwrap_or_else(|| cover.clone()),
+                Color::rgb(ALBUM_ART_DIMMING, ALBUM_ART_DIMMING, ALBUM_ART_DIMMING),
+            ),
+        };
+        // Hidden until it is sized below, next frame
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite { color, ..default() },
+                texture: images.add(image),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            AlbumArtSprite {
+                aspect: cover.width() as f32 / cover.height().max(1) as f32,
+            },
+        ));
+        return;
+    }
+
+    let Ok(window) = window.get_single() else {
+        return;
+    };
+    let (w, h) = (window.width(), window.height());
+    for (_, art, mut sprite, mut transform, mut visibility) in &mut sprites {
+        *visibility = Visibility::Visible;
+        match args.album_art {
+            // Covers the whole window, cropping the cover's edges
+            AlbumArt::Background => {
+                sprite.custom_size = Some(match w / h > art.aspect {
+                    true => Vec2::new(w, w / art.aspect),
+                    false => Vec2::new(h * art.aspect, h),
+                });
+                transform.translation = Vec3::new(0.0, 0.0, -10.0);
+            }
+            _ => {
+                let size = w.min(h) * ALBUM_ART_SIZE;
+                sprite.custom_size = Some(Vec2::new(size * art.aspect, size));
+                sprite.anchor = Anchor::TopRight;
+                transform.translation = Vec3::new(w / 2.0 - 10.0, h / 2.0 - 10.0, 5.0);
+            }
+        }
+    }
+}
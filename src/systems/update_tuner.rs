@@ -0,0 +1,105 @@
+use crate::*;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy::sprite::Anchor;
+
+#[derive(Component)]
+pub struct TunerText;
+
+pub fn spawn_tuner(mut commands: Commands, args: Res<FFTArgs>) {
+    let note_style = TextStyle {
+        font: Default::default(),
+        font_size: args.font_size as f32 * 2.0,
+        color: args.text_color,
+    };
+    let detail_style = TextStyle {
+        font: Default::default(),
+        font_size: args.font_size as f32 * 0.8,
+        color: args.text_color,
+    };
+
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_sections([
+                TextSection::new("", note_style),
+                TextSection::new("", detail_style),
+            ])
+            .with_justify(JustifyText::Center),
+            text_anchor: Anchor::TopCenter,
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        TunerText,
+    ));
+}
+
+pub fn update_tuner(
+    mut gizmos: Gizmos,
+    window: Query<&Window, With<PrimaryWindow>>,
+    audio: Res<AudioBuffer>,
+    fft_state: Res<FFTState>,
+    args: Res<FFTArgs>,
+    mut tuner_text: Query<(&mut Text, &mut Transform, &mut Visibility), With<TunerText>>,
+    mut last_pitch: Local<(usize, Option<f32>)>,
+) {
+    let Ok((mut text, mut transform, mut visibility)) = tuner_text.get_single_mut() else {
+        return;
+    };
+    if !args.tuner {
+        *visibility = Visibility::Hidden;
+        return;
+    }
+    *visibility = Visibility::Visible;
+
+    // Pitch is re-estimated once per fft frame
+    if last_pitch.0 != fft_state.fft_frame_counter {
+        let window_len = 2 * (audio.sample_rate as f32 / TUNER_MIN_FREQ) as usize;
+        let end = (fft_state.elapsed() * audio.sample_rate as f64) as usize;
+        let samples = audio.mono_window(end, window_len);
+        *last_pitch = (
+            fft_state.fft_frame_counter,
+            detect_pitch(&samples, audio.sample_rate, TUNER_MIN_FREQ, TUNER_MAX_FREQ),
+        );
+    }
+
+    let Ok(window) = window.get_single() else {
+        return;
+    };
+    let top = window.height() / 2.0 - 10.0;
+    transform.translation = Vec3::new(0.0, top, 1.0);
+
+    let Some(freq) = last_pitch.1 else {
+        text.sections[0].value = String::from("--");
+        text.sections[1].value = String::new();
+        return;
+    };
+
+    let note = nearest_note(freq);
+    text.sections[0].value = format!("{}{}", note.name, note.octave);
+    text.sections[1].value = format!("\n{:+.0} cents  ({:.1} Hz)", note.cents, freq);
+
+    // Cents needle, -50 to +50
+    let scale_y = top - args.font_size as f32 * 3.5;
+    let half_width = 150.0;
+    let grid_color = args.text_color.with_a(0.4);
+    gizmos.line_2d(
+        Vec2::new(-half_width, scale_y),
+        Vec2::new(half_width, scale_y),
+        grid_color,
+    );
+    for x in [-half_width, 0.0, half_width] {
+        gizmos.line_2d(Vec2::new(x, scale_y - 6.0), Vec2::new(x, scale_y + 6.0), grid_color);
+    }
+
+    let needle_x = note.cents / 50.0 * half_width;
+    let needle_color = match note.cents.abs() {
+        c if c < TUNER_IN_TUNE_CENTS => Color::GREEN,
+        c if c < 4.0 * TUNER_IN_TUNE_CENTS => Color::YELLOW,
+        _ => Color::RED,
+    };
+    gizmos.line_2d(
+        Vec2::new(needle_x, scale_y - 12.0),
+        Vec2::new(needle_x, scale_y + 12.0),
+        needle_color,
+    );
+}
@@ -0,0 +1,91 @@
+use crate::*;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use std::f32::consts::FRAC_1_SQRT_2;
+
+// Width left for the spectrum, which starts at the left edge of the window.
+// The vectorscope takes a column on the right so it never covers the bars.
+pub fn spectrum_width(w: f32, h: f32, args: &FFTArgs) -> f32 {
+    match args.vectorscope && !args.overlay {
+        true => w - (w.min(h) * VECTORSCOPE_SIZE + 20.0),
+        false => w,
+    }
+}
+
+pub fn update_vectorscope(
+    mut gizmos: Gizmos,
+    window: Query<&Window, With<PrimaryWindow>>,
+    audio: Res<AudioBuffer>,
+    fft_state: Res<FFTState>,
+    args: Res<FFTArgs>,
+) {
+    if !args.vectorscope || args.overlay {
+        return;
+    }
+
+    let Ok(window) = window.get_single() else {
+        return;
+    };
+    let (w, h) = (window.width(), window.height());
+    let size = w.min(h) * VECTORSCOPE_SIZE;
+    let half = size / 2.0;
+
+    // Bottom of the right column beside the spectrum, leaving room for the
+    // correlation meter underneath
+    let center = Vec2::new(w / 2.0 - half - 10.0, -h / 2.0 + half + 30.0);
+    let grid_color = args.text_color.with_a(0.3);
+
+    gizmos.rect_2d(center, 0.0, Vec2::splat(size), grid_color);
+    gizmos.line_2d(center - Vec2::Y * half, center + Vec2::Y * half, grid_color);
+    gizmos.line_2d(center - Vec2::X * half, center + Vec2::X * half, grid_color);
+
+    // Samples plotted this frame, ending at the current playback position
+    let sample_rate = audio.sample_rate as f64;
+    let elapsed = fft_state.elapsed();
+    let end = ((elapsed * sample_rate) as usize).min(audio.num_frames());
+    let start = end.saturating_sub((VECTORSCOPE_WINDOW * sample_rate) as usize);
+
+    // Each sample is its own dot, joining them would trace lines across the
+    // scope that aren't in the signal
+    let (mut lr, mut ll, mut rr) = (0.0, 0.0, 0.0);
+    for i in start..end {
+        let (l, r) = audio.stereo_frame(i);
+        lr += l * r;
+        ll += l * l;
+        rr += r * r;
+
+        // Rotated 45 degrees so mono content sits on the vertical axis
+        let p = Vec2::new((r - l) * FRAC_1_SQRT_2, (l + r) * FRAC_1_SQRT_2);
+        let p = center + (p * half).clamp(Vec2::splat(-half), Vec2::splat(half));
+        gizmos.circle_2d(p, 1.0, args.bar_color).segments(6);
+    }
+
+    // Correlation meter: -1 (out of phase) to +1 (mono)
+    let correlation = match ll * rr {
+        d if d > 0.0 => lr / d.sqrt(),
+        _ => 0.0,
+    };
+    let meter_y = center.y - half - 15.0;
+    gizmos.line_2d(
+        Vec2::new(center.x - half, meter_y),
+        Vec2::new(center.x + half, meter_y),
+        grid_color,
+    );
+    gizmos.line_2d(
+        Vec2::new(center.x, meter_y - 5.0),
+        Vec2::new(center.x, meter_y + 5.0),
+        grid_color,
+    );
+
+    let marker_x = center.x + correlation.clamp(-1.0, 1.0) * half;
+    let marker_color = if correlation < 0.0 {
+        Color::RED
+    } else {
+        Color::GREEN
+    };
+    gizmos.line_2d(
+        Vec2::new(marker_x, meter_y - 8.0),
+        Vec2::new(marker_x, meter_y + 8.0),
+        marker_color,
+    );
+}
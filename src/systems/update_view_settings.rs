@@ -1,29 +1,40 @@
+use crate::systems::startup::TrackNameText;
 use crate::*;
 use bevy::render::mesh::VertexAttributeValues;
 use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
 
+#[allow(clippy::too_many_arguments)]
 pub fn update_view_settings(
-    mut window: Query<&mut Window>,
+    window: Query<&Window, With<PrimaryWindow>>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
     app_state: Res<FFTState>,
     args: Res<FFTArgs>,
-    mut clear_color: ResMut<ClearColor>,
-    mut text_query: Query<(&mut Transform, &mut Text)>,
+    mut text_query: Query<(&mut Transform, &mut Text), With<TrackNameText>>,
     mut differencing_args_query: Query<&mut FFTArgs>,
     mut bar_query: Query<&mut Transform, Without<Text>>,
 ) {
-    let mut differencing_args = differencing_args_query.get_single_mut().unwrap();
-
-    // Update bar sizes and positions on resize
-    let w = window.single_mut().width();
-    if differencing_args.window_width != w {
-        let h = window.single_mut().height();
+    let Ok(window) = window.get_single() else {
+        return;
+    };
+    let Ok(mut differencing_args) = differencing_args_query.get_single_mut() else {
+        return;
+    };
+
+    // Update bar sizes and positions on resize, or when the vectorscope
+    // column beside the spectrum comes or goes
+    let (w, h) = (window.width(), window.height());
+    let resized = differencing_args.window_width != w
+        || differencing_args.window_height != h
+        || differencing_args.vectorscope != args.vectorscope
+        || differencing_args.overlay != args.overlay;
+    if resized {
         let mut text = text_query.get_single_mut().unwrap().0;
         text.translation.x = 10.0 - w / 2.0;
         text.translation.y = h / 2.0 - 10.0;
 
-        let bar_size = w / app_state.fft[0].len() as f32;
+        let bar_size = spectrum_width(w, h, &args) / app_state.fft[0].len() as f32;
         for (i, b) in app_state.despawn_handles.chunks(2).enumerate() {
             bar_query.get_mut(b[0]).unwrap().translation.x =
                 bar_size * i as f32 + bar_size / 2.0 - w / 2.0;
@@ -44,14 +55,11 @@ pub fn update_view_settings(
                 .attribute_mut(Mesh::ATTRIBUTE_POSITION)
                 .unwrap();
 
-            match dims {
-                VertexAttributeValues::Float32x3(x) => {
-                    x[0][0] = outer_bar_size;
-                    x[1][0] = -outer_bar_size;
-                    x[2][0] = -outer_bar_size;
-                    x[3][0] = outer_bar_size;
-                }
-                _ => {}
+            if let VertexAttributeValues::Float32x3(x) = dims {
+                x[0][0] = outer_bar_size;
+                x[1][0] = -outer_bar_size;
+                x[2][0] = -outer_bar_size;
+                x[3][0] = outer_bar_size;
             }
 
             let dims = meshes
@@ -60,17 +68,17 @@ pub fn update_view_settings(
                 .attribute_mut(Mesh::ATTRIBUTE_POSITION)
                 .unwrap();
 
-            match dims {
-                VertexAttributeValues::Float32x3(x) => {
-                    x[0][0] = inner_bar_size;
-                    x[1][0] = -inner_bar_size;
-                    x[2][0] = -inner_bar_size;
-                    x[3][0] = inner_bar_size;
-                }
-                _ => {}
+            if let VertexAttributeValues::Float32x3(x) = dims {
+                x[0][0] = inner_bar_size;
+                x[1][0] = -inner_bar_size;
+                x[2][0] = -inner_bar_size;
+                x[3][0] = inner_bar_size;
             }
         }
         differencing_args.window_width = w;
+        differencing_args.window_height = h;
+        differencing_args.vectorscope = args.vectorscope;
+        differencing_args.overlay = args.overlay;
     }
 
     // Update text color + visibility + size
@@ -79,7 +87,8 @@ pub fn update_view_settings(
         || differencing_args.font_size != args.font_size
     {
         for mut text in &mut text_query {
-            if args.track_name {
+            // The overlay only shows the bars
+            if args.track_name && !args.overlay {
                 text.1.sections[0].style.color = args.text_color;
                 text.1.sections[0].style.font_size = args.font_size as f32;
             } else {
@@ -92,12 +101,6 @@ pub fn update_view_settings(
         differencing_args.track_name = args.track_name;
     }
 
-    // Update background color
-    if differencing_args.background_color != args.background_color {
-        clear_color.0 = args.background_color;
-        differencing_args.background_color = args.background_color;
-    }
-
     // Update bar colors
     if differencing_args.bar_color != args.bar_color
         || differencing_args.border_color != args.border_color
@@ -114,9 +117,9 @@ pub fn update_view_settings(
 
     // Update border size
     if differencing_args.border_size != args.border_size {
-        let w = window.single_mut().width();
+        let w = spectrum_width(window.width(), window.height(), &args);
         let bar_size =
-            ((w as f32 / (app_state.curr_bars.len() / 2) as f32) - args.border_size as f32) / 2.0;
+            ((w / (app_state.curr_bars.len() / 2) as f32) - args.border_size as f32) / 2.0;
 
         for handle in app_state.curr_bars.chunks(2) {
             let handle1 = handle[1].0.clone_weak();
@@ -127,17 +130,22 @@ pub fn update_view_settings(
                 .attribute_mut(Mesh::ATTRIBUTE_POSITION)
                 .unwrap();
 
-            match dims {
-                VertexAttributeValues::Float32x3(x) => {
-                    x[0][0] = bar_size;
-                    x[1][0] = -bar_size;
-                    x[2][0] = -bar_size;
-                    x[3][0] = bar_size;
-                }
-                _ => {}
+            if let VertexAttributeValues::Float32x3(x) = dims {
+                x[0][0] = bar_size;
+                x[1][0] = -bar_size;
+                x[2][0] = -bar_size;
+                x[3][0] = bar_size;
             }
         }
 
         differencing_args.border_size = args.border_size;
     }
 }
+
+// Keeps ClearColor on the background color, when the plugin sets it
+pub fn update_clear_color(args: Res<FFTArgs>, mut clear_color: ResMut<ClearColor>) {
+    let color = background_color(&args);
+    if args.is_changed() && clear_color.0 != color {
+        clear_color.0 = color;
+    }
+}
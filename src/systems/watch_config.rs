@@ -0,0 +1,98 @@
+use crate::systems::recompute_fft::{analysis_settings_changed, FFTRecompute};
+use crate::*;
+use bevy::prelude::*;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+use std::time::{Duration, Instant};
+
+// Time to wait after the last change before reloading, since editors often
+// write a file in several steps
+const RELOAD_DELAY: Duration = Duration::from_millis(250);
+
+// Watches the config file's directory (not the file itself, which editors
+// replace when saving) for changes to the config file
+pub struct ConfigWatcher {
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+    cfg_path: PathBuf,
+}
+
+impl ConfigWatcher {
+    pub fn new(cfg_path: &Path) -> notify::Result<Self> {
+        let (tx, rx) = channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            let _ = tx.send(event);
+        })?;
+        let dir = match cfg_path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+
+        Ok(ConfigWatcher {
+            _watcher: watcher,
+            events: rx,
+            cfg_path: cfg_path.to_path_buf(),
+        })
+    }
+
+    // Whether the config file changed since the last call
+    fn config_changed(&self) -> bool {
+        let cfg_path = &self.cfg_path;
+        self.events
+            .try_iter()
+            .filter_map(|event| event.ok())
+            .filter(|event| !matches!(event.kind, EventKind::Access(_)))
+            .any(|event| event.paths.iter().any(|p| p.file_name() == cfg_path.file_name()))
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn watch_config(
+    watcher: Option<NonSend<ConfigWatcher>>,
+    mut pending_since: Local<Option<Instant>>,
+    mut args: ResMut<FFTArgs>,
+    mut layers: ResMut<ArgLayers>,
+    mut app_state: ResMut<AppState>,
+    mut recompute: ResMut<FFTRecompute>,
+    fft_state: Res<FFTState>,
+    audio_buffer: Res<AudioBuffer>,
+) {
+    let Some(watcher) = watcher else {
+        return;
+    };
+    if watcher.config_changed() {
+        *pending_since = Some(Instant::now());
+    }
+    match *pending_since {
+        Some(t) if t.elapsed() >= RELOAD_DELAY => *pending_since = None,
+        _ => return,
+    }
+
+    // Only settings the edit changed are applied, so toggles, volume and
+    // speed changed in the app are kept. Visual settings are applied by
+    // update_view_settings, analysis settings need the spectrum to be recomputed.
+    let previous = layers.resolved.clone();
+    app_state.display_str = match layers
+        .resolve(args.profile.clone(), true)
+        .and_then(|_| {
+            let changed = layers.resolved.clone().overrides_of(&previous);
+            let values = changed.inherit(&fftargs_to_config(&args, &previous));
+            layers.fft_args(values, args.profile.clone())
+        }) {
+        Ok(new_args) => {
+            if new_args == *args {
+                return;
+            }
+            if analysis_settings_changed(&args, &new_args) {
+                recompute.start(&audio_buffer, &new_args);
+            }
+            app_state.sink.set_volume(new_args.volume as f32 / 100.0);
+            *args = new_args;
+            String::from("Reloaded config.")
+        }
+        Err(e) => e.to_string(),
+    };
+    app_state.display_start_time = fft_state.fft_timer.elapsed().as_secs_f64();
+}
@@ -0,0 +1,176 @@
+use crate::FFTArgs;
+use bevy::prelude::*;
+#[cfg(target_os = "macos")]
+use bevy::window::CompositeAlphaMode;
+use bevy::window::{
+    Cursor, EnabledButtons, MonitorSelection, PrimaryWindow, WindowLevel, WindowMode,
+    WindowPosition,
+};
+use bevy::winit::WinitWindows;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Fullscreen {
+    Off,
+    // Exclusive fullscreen, changing the monitor's video mode
+    Exclusive,
+    // A borderless window covering the monitor
+    Borderless,
+}
+
+impl Fullscreen {
+    pub fn window_mode(&self) -> WindowMode {
+        match self {
+            Fullscreen::Off => WindowMode::Windowed,
+            Fullscreen::Exclusive => WindowMode::Fullscreen,
+            Fullscreen::Borderless => WindowMode::BorderlessFullscreen,
+        }
+    }
+}
+
+// Where the window opens
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Placement {
+    // Centered on the chosen monitor
+    Centered,
+    // Top left corner at a position in pixels on the desktop
+    At(IVec2),
+}
+
+impl Placement {
+    // Parses "center" or "x,y"
+    pub fn parse(value: &str) -> Option<Placement> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("center") {
+            return Some(Placement::Centered);
+        }
+        let (x, y) = value.split_once(',')?;
+        Some(Placement::At(IVec2::new(
+            x.trim().parse().ok()?,
+            y.trim().parse().ok()?,
+        )))
+    }
+}
+
+impl fmt::Display for Placement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Placement::Centered => write!(f, "center"),
+            Placement::At(p) => write!(f, "{},{}", p.x, p.y),
+        }
+    }
+}
+
+pub fn window_title(file_path: &Path) -> String {
+    format!(
+        "fftviz - {}",
+        file_path.file_stem().unwrap_or_default().to_string_lossy()
+    )
+}
+
+// Color the window is cleared to. The overlay only draws the bars.
+pub fn background_color(args: &FFTArgs) -> Color {
+    match args.overlay {
+        true => Color::NONE,
+        false => args.background_color,
+    }
+}
+
+// Primary window for `args`. It opens windowed on the chosen monitor, and
+// apply_fullscreen then makes it fullscreen there: bevy would otherwise
+// always open fullscreen windows on the primary monitor. The overlay is
+// transparent, undecorated and lets clicks through to the windows below;
+// pin_overlay then moves it to the bottom of the monitor.
+pub fn primary_window(args: &FFTArgs) -> Window {
+    let transparent = args.transparent || args.overlay;
+    Window {
+        title: window_title(&args.file_path),
+        name: Some("fftviz".into()),
+        decorations: args.title_bar && !args.overlay,
+        resolution: (args.window_width, args.window_height).into(),
+        position: match args.position {
+            Placement::Centered => WindowPosition::Centered(MonitorSelection::Index(args.monitor)),
+            Placement::At(p) => WindowPosition::At(p),
+        },
+        window_level: match args.always_on_top || args.overlay {
+            true => WindowLevel::AlwaysOnTop,
+            false => WindowLevel::Normal,
+        },
+        cursor: Cursor {
+            hit_test: !args.overlay,
+            ..default()
+        },
+        focused: !args.overlay,
+        // The background shows through where background_color is transparent
+        transparent,
+        #[cfg(target_os = "macos")]
+        composite_alpha_mode: match transparent {
+            true => CompositeAlphaMode::PostMultiplied,
+            false => CompositeAlphaMode::Auto,
+        },
+        prevent_default_event_handling: false,
+        enabled_buttons: EnabledButtons {
+            maximize: false,
+            ..Default::default()
+        },
+        visible: true,
+        ..default()
+    }
+}
+
+// Startup system switching the window opened by primary_window to args.fullscreen
+pub fn apply_fullscreen(
+    mut window: Query<&mut Window, With<PrimaryWindow>>,
+    args: Res<FFTArgs>,
+) {
+    if args.overlay {
+        return;
+    }
+    if let Ok(mut window) = window.get_single_mut() {
+        window.mode = args.fullscreen.window_mode();
+    }
+}
+
+// Stretches the overlay across the bottom of its monitor, keeping its
+// height. The monitor is only known once winit has created the window.
+pub fn pin_overlay(
+    mut window: Query<(Entity, &mut Window), With<PrimaryWindow>>,
+    winit_windows: NonSend<WinitWindows>,
+    args: Res<FFTArgs>,
+    mut pinned: Local<bool>,
+) {
+    if !args.overlay || *pinned {
+        return;
+    }
+    let Ok((entity, mut window)) = window.get_single_mut() else {
+        return;
+    };
+    let Some(monitor) = winit_windows
+        .get_window(entity)
+        .and_then(|w| w.current_monitor().or_else(|| w.primary_monitor()))
+    else {
+        return;
+    };
+
+    let (size, origin) = (monitor.size(), monitor.position());
+    let height = (args.window_height as f64 * monitor.scale_factor()) as u32;
+    window.resolution.set_physical_resolution(size.width, height);
+    window.position = WindowPosition::At(IVec2::new(
+        origin.x,
+        origin.y + size.height as i32 - height as i32,
+    ));
+    *pinned = true;
+}
+
+// Leaves fullscreen, or enters args.fullscreen (borderless if that is off)
+pub fn toggle_fullscreen(window: &mut Window, fullscreen: Fullscreen) {
+    window.mode = match (window.mode, fullscreen) {
+        (WindowMode::Windowed, Fullscreen::Off) => WindowMode::BorderlessFullscreen,
+        (WindowMode::Windowed, fullscreen) => fullscreen.window_mode(),
+        _ => WindowMode::Windowed,
+    };
+}